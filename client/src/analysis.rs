@@ -0,0 +1,347 @@
+//! Reviewing a finished game: the engine searches every position in the background, and the
+//! moves can be stepped through with their evaluation, the best alternative, and whether they
+//! were inaccuracies, mistakes or blunders.
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use crossterm::event::{KeyCode, KeyEvent};
+use engine::{
+    alpha_beta::MATE,
+    analysis::{ANALYSIS_DEPTH, GameAnalysis, Judgement, PlyAnalysis},
+};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Margin, Rect},
+    style::{Color, Style, Stylize},
+    symbols::Marker,
+    text::Line,
+    widgets::{Axis, Chart, Dataset, GraphType, Paragraph, Widget},
+};
+use store::{
+    board::Board,
+    game_state::{ClientEvent, GameState},
+    game_utils::Move,
+    pdn::notation,
+    player::{Player, PlayerId},
+};
+
+use client::IncomingEvent;
+
+use crate::board::render_board;
+
+/// evaluations beyond this many pawns are drawn at the edge of the graph
+const GRAPH_PAWNS: f64 = 5.0;
+
+#[derive(Debug)]
+pub struct AnalysisScene {
+    players: HashMap<PlayerId, Player>,
+    // every position of the game, the final one included, and the moves between them
+    positions: Vec<Board>,
+    moves: Vec<Move>,
+    // the moves analysed so far, in order
+    plies: Vec<PlyAnalysis>,
+    // the position shown, before the move of the same index
+    current: usize,
+    player_id: PlayerId,
+    // set when the scene is left, so that the analysis stops
+    cancelled: Arc<AtomicBool>,
+}
+
+impl AnalysisScene {
+    pub fn new(
+        game_state: GameState,
+        player_id: PlayerId,
+        events_tx: mpsc::Sender<IncomingEvent>,
+    ) -> Self {
+        let mut board = game_state.initial_position().0.clone();
+        let mut positions = vec![board.clone()];
+        let moves = game_state.moves().cloned().collect::<Vec<_>>();
+        for mv in &moves {
+            board.apply(mv).unwrap();
+            positions.push(board.clone());
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let analysis = GameAnalysis::new(&game_state, ANALYSIS_DEPTH);
+        let stop = cancelled.clone();
+        thread::spawn(move || {
+            for (ply, analysis) in analysis.enumerate() {
+                if stop.load(Ordering::Relaxed)
+                    || events_tx
+                        .send(IncomingEvent::PlyAnalysed { ply, analysis })
+                        .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            // as seated at the start, a player who left the game being gone from `players`
+            players: game_state.initial_players().clone(),
+            positions,
+            moves,
+            plies: vec![],
+            current: 0,
+            player_id,
+            cancelled,
+        }
+    }
+
+    pub fn handle_analysis(&mut self, ply: usize, analysis: PlyAnalysis) {
+        // results of an analysis left earlier may still be on their way
+        if ply == self.plies.len() && self.moves.get(ply) == Some(&analysis.mv) {
+            self.plies.push(analysis);
+        }
+    }
+
+    pub fn handle_input(&mut self, key_event: KeyEvent) -> Option<ClientEvent> {
+        match key_event.code {
+            KeyCode::Left => self.current = self.current.saturating_sub(1),
+            KeyCode::Right => self.current = (self.current + 1).min(self.moves.len()),
+            KeyCode::Home => self.current = 0,
+            KeyCode::End => self.current = self.moves.len(),
+            KeyCode::Char('n') => {
+                if let Some(ply) =
+                    (self.current + 1..self.plies.len()).find(|ply| self.flagged(*ply))
+                {
+                    self.current = ply;
+                }
+            }
+            KeyCode::Char('p') => {
+                if let Some(ply) = (0..self.current).rev().find(|ply| self.flagged(*ply)) {
+                    self.current = ply;
+                }
+            }
+            KeyCode::Esc => return Some(ClientEvent::LeaveAnalysis),
+            _ => {}
+        }
+        None
+    }
+
+    fn flagged(&self, ply: usize) -> bool {
+        self.plies
+            .get(ply)
+            .is_some_and(|analysis| analysis.judgement.is_some())
+    }
+
+    /// the score of a position for the player reviewing the game, when already searched
+    fn score(&self, position: usize) -> Option<i32> {
+        let (analysis, score) = match self.plies.get(position) {
+            Some(analysis) => (analysis, analysis.score),
+            None => {
+                let analysis = self.plies.get(position.checked_sub(1)?)?;
+                (analysis, analysis.score_after)
+            }
+        };
+        Some(if analysis.player_id == self.player_id {
+            score
+        } else {
+            -score
+        })
+    }
+
+    fn name(&self, player_id: PlayerId) -> String {
+        self.players
+            .get(&player_id)
+            .map_or_else(|| player_id.to_string(), |player| player.name.clone())
+    }
+
+    /// how many moves of `player_id` got each judgement
+    fn summary(&self, player_id: PlayerId) -> Line<'static> {
+        let count = |judgement| {
+            self.plies
+                .iter()
+                .filter(|analysis| {
+                    analysis.player_id == player_id && analysis.judgement == Some(judgement)
+                })
+                .count()
+        };
+        Line::from(vec![
+            format!("{}: ", self.name(player_id)).fg(if player_id == self.player_id {
+                Color::Green
+            } else {
+                Color::Red
+            }),
+            format!("{} inaccuracies, ", count(Judgement::Inaccuracy)).into(),
+            format!("{} mistakes, ", count(Judgement::Mistake)).into(),
+            format!("{} blunders", count(Judgement::Blunder)).into(),
+        ])
+    }
+
+    /// the move leading from the position shown to the next one, as analysed
+    fn describe_move(&self) -> Line<'static> {
+        let Some(mv) = self.moves.get(self.current) else {
+            return Line::from("Final position");
+        };
+        let board = &self.positions[self.current];
+        let mut spans = vec![
+            format!("Move {}/{}: ", self.current + 1, self.moves.len()).into(),
+            notation(board, mv).yellow().bold(),
+        ];
+        match self.plies.get(self.current) {
+            Some(analysis) => {
+                spans.push(format!(" by {}", self.name(analysis.player_id)).into());
+                if let Some(judgement) = analysis.judgement {
+                    spans.push(", ".into());
+                    spans.push(judgement.to_string().fg(judgement_color(judgement)).bold());
+                }
+                spans.push(format!(" ({})", describe_score(analysis.score_after)).into());
+                if analysis.best != analysis.mv {
+                    spans.push(", best was ".into());
+                    spans.push(notation(board, &analysis.best).light_blue().bold());
+                    spans.push(format!(" ({})", describe_score(analysis.score)).into());
+                }
+            }
+            None => spans.push(", searching...".gray()),
+        }
+        Line::from(spans)
+    }
+}
+
+impl Drop for AnalysisScene {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Widget for &AnalysisScene {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let vertical_layout = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Percentage(25),
+            Constraint::Fill(1),
+        ])
+        .spacing(1);
+        let [info_area, chart_area, board_area] =
+            vertical_layout.areas(area.inner(Margin::new(1, 1)));
+
+        // info area
+        let mut players = self.players.keys().copied().collect::<Vec<_>>();
+        players.sort_by_key(|player_id| *player_id != self.player_id);
+        let mut lines = players
+            .into_iter()
+            .map(|player_id| self.summary(player_id))
+            .collect::<Vec<_>>();
+        if self.plies.len() < self.moves.len() {
+            lines.push(
+                Line::from(format!(
+                    "Analysing: {}/{} moves",
+                    self.plies.len(),
+                    self.moves.len()
+                ))
+                .gray(),
+            );
+        }
+        lines.push(self.describe_move());
+        lines.push(Line::from(vec![
+            "Step ".into(),
+            "<Left/Right>".blue().bold(),
+            " Next/previous mistake ".into(),
+            "<N/P>".blue().bold(),
+            " Back ".into(),
+            "<Esc>".blue().bold(),
+        ]));
+        Paragraph::new(lines).render(info_area, buf);
+
+        // evaluation graph, in pawns for the player reviewing the game
+        let limit = GRAPH_PAWNS;
+        let pawns = |score: i32| (score as f64 / 100.0).clamp(-limit, limit);
+        let evaluation = (0..self.positions.len())
+            .filter_map(|position| Some((position as f64, pawns(self.score(position)?))))
+            .collect::<Vec<_>>();
+        let flagged = |judgement| {
+            self.plies
+                .iter()
+                .enumerate()
+                .filter(|(_, analysis)| analysis.judgement == Some(judgement))
+                .filter_map(|(ply, _)| Some(((ply + 1) as f64, pawns(self.score(ply + 1)?))))
+                .collect::<Vec<_>>()
+        };
+        let inaccuracies = flagged(Judgement::Inaccuracy);
+        let mistakes = flagged(Judgement::Mistake);
+        let blunders = flagged(Judgement::Blunder);
+        let moves = self.moves.len().max(1) as f64;
+        let zero = [(0.0, 0.0), (moves, 0.0)];
+        let current = [(self.current as f64, -limit), (self.current as f64, limit)];
+
+        let line = |data, color| {
+            Dataset::default()
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(color))
+                .data(data)
+        };
+        let points = |data, color| {
+            Dataset::default()
+                .marker(Marker::Dot)
+                .graph_type(GraphType::Scatter)
+                .style(Style::default().fg(color))
+                .data(data)
+        };
+        Chart::new(vec![
+            line(&zero, Color::DarkGray),
+            line(&current, Color::Cyan),
+            line(&evaluation, Color::White),
+            points(&inaccuracies, judgement_color(Judgement::Inaccuracy)),
+            points(&mistakes, judgement_color(Judgement::Mistake)),
+            points(&blunders, judgement_color(Judgement::Blunder)),
+        ])
+        .x_axis(Axis::default().bounds([0.0, moves]))
+        .y_axis(Axis::default().bounds([-limit, limit]).labels([
+            format!("-{limit}"),
+            "0".to_string(),
+            format!("+{limit}"),
+        ]))
+        .render(chart_area, buf);
+
+        // board, with the move played from there and the one the engine preferred
+        let played = self.moves.get(self.current).map(Move::path);
+        let best = self
+            .plies
+            .get(self.current)
+            .filter(|analysis| analysis.best != analysis.mv)
+            .map(|analysis| analysis.best.path());
+        render_board(
+            &self.positions[self.current],
+            self.player_id,
+            |coords| {
+                if played.as_ref().is_some_and(|path| path.contains(&coords)) {
+                    Some(Color::Yellow)
+                } else if best.as_ref().is_some_and(|path| path.contains(&coords)) {
+                    Some(Color::LightBlue)
+                } else {
+                    None
+                }
+            },
+            board_area,
+            buf,
+        );
+    }
+}
+
+fn judgement_color(judgement: Judgement) -> Color {
+    match judgement {
+        Judgement::Inaccuracy => Color::LightYellow,
+        Judgement::Mistake => Color::LightRed,
+        Judgement::Blunder => Color::Red,
+    }
+}
+
+/// a score in pawns for the player moving, or the plies to the end of the game once it is decided
+fn describe_score(score: i32) -> String {
+    let plies = MATE - score.abs();
+    if plies < MATE / 2 {
+        let outcome = if score > 0 { "wins" } else { "loses" };
+        format!("{outcome} in {plies} plies")
+    } else {
+        format!("{:+.2}", score as f64 / 100.0)
+    }
+}
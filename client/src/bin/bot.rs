@@ -0,0 +1,311 @@
+//! A client without a terminal, joining a server and playing by itself, with an engine or
+//! random moves, game after game:
+//! `cargo run --release --bin bot -- [--name bot] [--address 127.0.0.1:5000] [--engine alphabeta|mcts|mcts-heuristic|random] [--time ms] [--games n]`
+//! It declines draw offers, accepts takebacks, and logs the result of each game.
+use std::{collections::HashMap, iter, str::FromStr, sync::mpsc, thread, time::Duration};
+
+use cli_log::{LevelFilter, info};
+use client::{ClientToServerMessage, IncomingEvent, network::run_net_thread};
+use engine::{Engine, EngineKind, Limits};
+use rand::{SeedableRng, rngs::SmallRng, seq::SliceRandom};
+use store::{
+    game_state::{ClientEvent, EndGameReason, GameEvent, GameState},
+    game_utils::{Move, legal_moves},
+    opening_book::BOOK_FILE,
+    player::PlayerId,
+    rules::Variant,
+};
+
+const USAGE: &str = "Usage: bot [--name bot] [--address 127.0.0.1:5000] [--engine alphabeta|mcts|mcts-heuristic|random] [--time ms] [--games n]";
+/// how long to wait before joining the server again, so that it has reset after a game
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// how the bot picks its moves
+#[derive(Debug, Clone, Copy)]
+enum Policy {
+    Engine(EngineKind),
+    Random,
+}
+impl FromStr for Policy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(Policy::Random),
+            _ => Ok(Policy::Engine(s.parse()?)),
+        }
+    }
+}
+
+struct Options {
+    name: String,
+    address: String,
+    policy: Policy,
+    /// thinking time per move, for the engines
+    time: Duration,
+    /// games to play before stopping, all of them if `None`
+    games: Option<usize>,
+}
+
+struct Bot {
+    engine: Option<Box<dyn Engine + Send>>,
+    rng: SmallRng,
+    time: Duration,
+}
+impl Bot {
+    fn new(policy: Policy, time: Duration) -> Self {
+        Self {
+            engine: match policy {
+                Policy::Engine(kind) => {
+                    Some(kind.build_with(engine::default_book(), engine::default_tablebases()))
+                }
+                Policy::Random => None,
+            },
+            rng: SmallRng::from_entropy(),
+            time,
+        }
+    }
+    fn pick(&mut self, state: &GameState) -> Option<Move> {
+        match &mut self.engine {
+            Some(engine) => engine.best_move(state, Limits::time(self.time)),
+            None => legal_moves(&state.grid, state.is_turn)
+                .choose(&mut self.rng)
+                .cloned(),
+        }
+    }
+}
+
+/// how the games played so far ended for the bot
+#[derive(Debug, Default)]
+struct Record {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    /// games cut short by a lost connection
+    aborted: usize,
+}
+
+fn main() {
+    env_logger::Builder::from_default_env()
+        .filter_level(LevelFilter::Info)
+        .init();
+
+    if let Err(err) = run() {
+        eprintln!("❌ {err}\n{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args()?;
+    let mut bot = Bot::new(options.policy, options.time);
+    if matches!(options.policy, Policy::Engine(_)) {
+        if engine::default_book().is_some() {
+            info!("📚 Playing the first moves from {BOOK_FILE}");
+        }
+        if let Some(tablebases) = engine::default_tablebases() {
+            info!(
+                "📚 Playing endgames up to {} pieces from the tablebases",
+                tablebases.max_pieces()
+            );
+        }
+    }
+    let mut record = Record::default();
+    let mut played = 0;
+    while options.games.is_none_or(|games| played < games) {
+        let game = play_game(&options, &mut bot);
+        if let Ok(None) = game {
+            info!("🔌 Could not join the server, trying again");
+            thread::sleep(RECONNECT_DELAY);
+            continue;
+        }
+        played += 1;
+        match game {
+            Ok(None) => {}
+            Ok(Some((player_id, state, reason))) => {
+                let result = match reason {
+                    EndGameReason::PlayerWon { winner } if winner == player_id => {
+                        record.wins += 1;
+                        "won".to_string()
+                    }
+                    EndGameReason::PlayerWon { .. } => {
+                        record.losses += 1;
+                        "lost".to_string()
+                    }
+                    EndGameReason::PlayerLeft { player_id: left } if left != player_id => {
+                        record.wins += 1;
+                        "won, the opponent left".to_string()
+                    }
+                    EndGameReason::PlayerLeft { .. } => {
+                        record.losses += 1;
+                        "lost, having left".to_string()
+                    }
+                    EndGameReason::Draw { kind } => {
+                        record.draws += 1;
+                        format!("drawn ({kind:?})")
+                    }
+                };
+                info!(
+                    "🏁 Game {played} {result} after {} moves: {} wins, {} draws, {} losses, {} aborted",
+                    state.moves().count(),
+                    record.wins,
+                    record.draws,
+                    record.losses,
+                    record.aborted
+                );
+            }
+            Err(err) => {
+                record.aborted += 1;
+                info!("❌ Game {played} aborted: {err}");
+            }
+        }
+        thread::sleep(RECONNECT_DELAY);
+    }
+    Ok(())
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        name: "bot".to_string(),
+        address: "127.0.0.1:5000".to_string(),
+        policy: Policy::Engine(EngineKind::default()),
+        time: Duration::from_secs(1),
+        games: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {arg}"));
+        match arg.as_str() {
+            "--name" => options.name = value()?,
+            "--address" => options.address = value()?,
+            "--engine" => options.policy = value()?.parse()?,
+            "--time" => {
+                let time = value()?;
+                let millis = time
+                    .parse()
+                    .map_err(|_| format!("Invalid time in milliseconds: {time}"))?;
+                options.time = Duration::from_millis(millis);
+            }
+            "--games" => {
+                let games = value()?;
+                options.games = Some(
+                    games
+                        .parse()
+                        .map_err(|_| format!("Invalid number of games: {games}"))?,
+                );
+            }
+            _ => return Err(format!("Unknown argument: {arg}")),
+        }
+    }
+    Ok(options)
+}
+
+/// joins the server and plays a game to its end, returning the bot's id, the game and how it
+/// ended; `None` if the connection closed before the game started
+fn play_game(
+    options: &Options,
+    bot: &mut Bot,
+) -> Result<Option<(PlayerId, GameState, EndGameReason)>, String> {
+    let (network_to_main_tx, network_to_main_rx) = mpsc::channel();
+    let (main_to_network_tx, main_to_network_rx) = mpsc::channel();
+    let (username, address) = (options.name.clone(), options.address.clone());
+    thread::spawn(move || {
+        run_net_thread(network_to_main_tx, main_to_network_rx, username, address);
+    });
+    let send = |event| {
+        main_to_network_tx
+            .send(ClientToServerMessage::SendEvent(event))
+            .map_err(|_| "The connection to the server was closed".to_string())
+    };
+
+    let mut player_id = 0;
+    let mut players = HashMap::new();
+    let mut variant = Variant::default();
+    let mut game_state: Option<GameState> = None;
+    let mut to_move = false;
+    loop {
+        // the events arrive in bursts, e.g. the moves of a ballot: the bot only thinks once it
+        // has caught up with the server
+        let Ok(first) = network_to_main_rx.recv() else {
+            return match game_state {
+                Some(_) => Err("The connection to the server was closed".to_string()),
+                None => Ok(None),
+            };
+        };
+        for event in iter::once(first).chain(network_to_main_rx.try_iter()) {
+            let event = match event {
+                IncomingEvent::ClientIdCommunication(client_id) => {
+                    player_id = client_id;
+                    continue;
+                }
+                IncomingEvent::ServerMessage(event) => event,
+                _ => continue,
+            };
+
+            let Some(state) = &mut game_state else {
+                match event {
+                    GameEvent::PlayerJoined { player } => {
+                        players.insert(player.id, player);
+                    }
+                    GameEvent::VariantAnnounced { variant: announced } => variant = announced,
+                    GameEvent::TurnChanged {
+                        player_id: starting_player,
+                    } => {
+                        info!("✨ The game starts, {variant}");
+                        let state = GameState::new(players.clone(), starting_player, variant);
+                        to_move = state.is_turn == player_id;
+                        game_state = Some(state);
+                    }
+                    GameEvent::GameResumed { log } => {
+                        info!("✨ The saved game resumes");
+                        let state = log.replay()?;
+                        to_move = state.is_turn == player_id;
+                        game_state = Some(state);
+                    }
+                    _ => {}
+                }
+                continue;
+            };
+
+            match event {
+                GameEvent::DrawOffered {
+                    player_id: offering,
+                } if offering != player_id => {
+                    send(GameEvent::DrawAnswered {
+                        player_id,
+                        accepted: false,
+                    })?;
+                }
+                GameEvent::TakebackRequested {
+                    player_id: requesting,
+                } if requesting != player_id => {
+                    send(GameEvent::TakebackAnswered {
+                        player_id,
+                        accepted: true,
+                    })?;
+                }
+                _ => {}
+            }
+            if let Some(ClientEvent::GoToMenu(reason)) = state.reduce(&event)? {
+                // the network thread may be gone already, the server having closed the game
+                let _ = main_to_network_tx.send(ClientToServerMessage::Disconnect);
+                return Ok(Some((player_id, state.clone(), reason)));
+            }
+            // the turn comes back with a new turn or a move taken back, and passes with a move
+            match event {
+                GameEvent::TurnChanged { .. } | GameEvent::TakebackAnswered { .. } => {
+                    to_move = state.is_turn == player_id;
+                }
+                GameEvent::Move { .. } => to_move = false,
+                _ => {}
+            }
+        }
+
+        if to_move && let Some(state) = &game_state {
+            to_move = false;
+            match bot.pick(state) {
+                Some(mv) => send(GameEvent::Move { mv, player_id })?,
+                None => info!("❌ No move to play"),
+            }
+        }
+    }
+}
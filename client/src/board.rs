@@ -0,0 +1,89 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint::Length, Flex, Layout, Rect},
+    style::{Color, Stylize},
+    symbols::Marker,
+    widgets::{
+        Block, BorderType, Widget,
+        canvas::{Canvas, Circle},
+    },
+};
+use store::{board::Board, coords::Coords, game_utils::coords_to_index, player::PlayerId};
+
+/// draws `board` as seen by `player_id`, their pieces in green, and the border of the cells
+/// in the color given by `highlight`, if any
+pub fn render_board(
+    board: &Board,
+    player_id: PlayerId,
+    highlight: impl Fn(Coords) -> Option<Color>,
+    area: Rect,
+    buf: &mut Buffer,
+) {
+    let (width, height) = (board.width, board.height);
+    let cell_size = area.height / height as u16;
+    let rows = Layout::vertical(vec![Length(cell_size); height])
+        .flex(Flex::Start)
+        .split(area);
+
+    let cells = rows
+        .iter()
+        .flat_map(|row| {
+            Layout::horizontal(vec![Length(cell_size * 2); width])
+                .flex(Flex::Center)
+                .split(*row)
+                .iter()
+                .copied()
+                .take(width)
+                .collect::<Vec<Rect>>()
+        })
+        .collect::<Vec<_>>();
+
+    for i in 0..width {
+        for j in 0..height {
+            let coords = Coords { x: i, y: j };
+            let c = &Circle {
+                x: 5.0,
+                y: 5.0,
+                color: if board[coords].is_some_and(|x| x.player_id == player_id) {
+                    Color::Green // player
+                } else {
+                    Color::Red // opponent
+                },
+                radius: 5.0,
+            };
+            let bg_color = if board.is_playable(coords) {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let border_color = highlight(coords).unwrap_or(bg_color);
+
+            Canvas::default()
+                .block(
+                    Block::bordered()
+                        .bg(bg_color)
+                        .fg(border_color)
+                        .border_type(BorderType::Double),
+                )
+                .marker(Marker::Braille)
+                .background_color(bg_color)
+                .x_bounds([0.0, 10.0])
+                .y_bounds([0.0, 10.0])
+                .paint(|ctx| {
+                    if let Some(piece) = board[coords] {
+                        ctx.draw(c);
+                        // kings get a crown ring inside the pawn
+                        if piece.is_king() {
+                            ctx.draw(&Circle {
+                                x: 5.0,
+                                y: 5.0,
+                                color: Color::Yellow,
+                                radius: 2.5,
+                            });
+                        }
+                    }
+                })
+                .render(cells[coords_to_index(coords, width)], buf);
+        }
+    }
+}
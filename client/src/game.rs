@@ -1,33 +1,48 @@
 use cli_log::info;
 use crossterm::event::{KeyCode, KeyEvent};
-use ratatui::{layout::Constraint::Length, style::Stylize, widgets::BorderType};
-use std::collections::HashMap;
+use engine::{EngineKind, Limits};
+use ratatui::{style::Stylize, text::Line};
+use std::{collections::HashMap, sync::mpsc, thread, time::Duration};
 
 use ratatui::{
     buffer::Buffer,
     layout::{Constraint, Layout, Margin, Rect},
     style::Color,
-    symbols::Marker,
-    widgets::{
-        Block, Paragraph, Widget,
-        canvas::{Canvas, Circle},
-    },
+    widgets::{Paragraph, Widget},
 };
 use store::{
-    CELL_N,
     coords::Coords,
+    event_log::EventLog,
     game_state::{ClientEvent, GameEvent, GameState},
-    game_utils::{Move, coords_to_index, get_possible_moves, is_white},
+    game_utils::{Move, legal_moves},
+    pdn::notation,
     player::{Player, PlayerId},
+    rules::Variant,
 };
 
+use client::IncomingEvent;
+
+use crate::board::render_board;
+
+/// how long the engine thinks about a hint
+const HINT_TIME: Duration = Duration::from_secs(1);
+
 #[derive(Debug)]
 pub struct GameScene {
-    game_state: GameState,
+    // boxed, as it is much bigger than the other scenes
+    game_state: Box<GameState>,
     possible_moves: Vec<Move>,
     cursor_cell: Coords,
     selected_cell: Option<Coords>,
+    // landing squares already picked while stepping through a capture sequence
+    selected_path: Vec<Coords>,
     player_id: PlayerId,
+    // hints are searched in another thread, and sent back to the main loop through this channel
+    events_tx: mpsc::Sender<IncomingEvent>,
+    hint: Option<Move>,
+    // the position a hint is being searched for
+    hint_pending: Option<u64>,
+    hints_used: usize,
 }
 
 impl GameScene {
@@ -35,25 +50,69 @@ impl GameScene {
         players: HashMap<PlayerId, Player>,
         player_id: PlayerId,
         starting_player: PlayerId,
+        variant: Variant,
+        events_tx: mpsc::Sender<IncomingEvent>,
     ) -> Self {
-        let player = players.get(&player_id).unwrap();
+        let game_state = GameState::new(players, starting_player, variant);
+        Self::from_state(game_state, player_id, events_tx)
+    }
+    /// the scene of a saved game, replayed from its log
+    pub fn resume(
+        log: &EventLog,
+        player_id: PlayerId,
+        events_tx: mpsc::Sender<IncomingEvent>,
+    ) -> Result<Self, String> {
+        let game_state = log.replay()?;
+        if !game_state.players.contains_key(&player_id) {
+            return Err(format!("Player {player_id} is not playing this game"));
+        }
+        Ok(Self::from_state(game_state, player_id, events_tx))
+    }
+    fn from_state(
+        game_state: GameState,
+        player_id: PlayerId,
+        events_tx: mpsc::Sender<IncomingEvent>,
+    ) -> Self {
+        let player = game_state.players.get(&player_id).unwrap();
         Self {
-            game_state: GameState::new(players.clone(), starting_player),
             cursor_cell: Coords {
                 x: 0,
-                y: if player.direction == -1 { 0 } else { 7 },
+                y: if player.direction == -1 {
+                    0
+                } else {
+                    game_state.grid.height - 1
+                },
             },
+            game_state: Box::new(game_state),
             selected_cell: None,
+            selected_path: vec![],
             player_id,
             possible_moves: vec![],
+            events_tx,
+            hint: None,
+            hint_pending: None,
+            hints_used: 0,
         }
     }
+    pub fn game_state(&self) -> &GameState {
+        &self.game_state
+    }
+    pub fn hints_used(&self) -> usize {
+        self.hints_used
+    }
     pub fn handle_input(&mut self, key_event: KeyEvent) -> Option<ClientEvent> {
         if self.game_state.players.len() < 2 {
             return None;
         }
         if key_event.code == KeyCode::Char(' ') && self.game_state.is_turn == self.player_id {
-            return self.select();
+            self.select()
+        } else if key_event.code == KeyCode::Char('h') {
+            self.toggle_hint();
+            None
+        } else if let KeyCode::Char(key @ ('t' | 'a' | 'r')) = key_event.code {
+            self.takeback_request(key)
+        } else if let KeyCode::Char(key @ ('d' | 'y' | 'n')) = key_event.code {
+            self.draw_offer(key)
         } else {
             match key_event.code {
                 KeyCode::Left => self.left(),
@@ -62,14 +121,16 @@ impl GameScene {
                 KeyCode::Right => self.right(),
                 _ => {}
             }
-            return None;
+            None
         }
     }
     pub fn handle_server_events(&mut self, game_event: GameEvent) -> Option<ClientEvent> {
         self.possible_moves.clear();
         self.selected_cell = None;
+        self.selected_path.clear();
+        self.hint = None;
         match self.game_state.reduce(&game_event) {
-            Ok(client_event) => return client_event,
+            Ok(client_event) => client_event,
             Err(err) => {
                 info!("❌ Error while reducing game event: {err}");
                 None
@@ -82,7 +143,7 @@ impl GameScene {
         }
     }
     fn down(&mut self) {
-        if self.cursor_cell.y < CELL_N - 1 {
+        if self.cursor_cell.y < self.game_state.grid.height - 1 {
             self.cursor_cell.y += 1;
         }
     }
@@ -92,47 +153,131 @@ impl GameScene {
         }
     }
     fn right(&mut self) {
-        if self.cursor_cell.x != CELL_N - 1 {
+        if self.cursor_cell.x != self.game_state.grid.width - 1 {
             self.cursor_cell.x += 1;
         }
     }
     fn select(&mut self) -> Option<ClientEvent> {
-        if !is_white(self.cursor_cell) {
+        if !self.game_state.grid.is_playable(self.cursor_cell) {
             return None;
         }
 
         // selecting empty cell
         if self.game_state.grid[self.cursor_cell].is_none() {
-            let selected_move = self
+            // the next landing square of a capture sequence: narrow down the possible moves
+            let step = self.selected_path.len() + 1;
+            let continuing = self
                 .possible_moves
                 .iter()
-                .find(|possible_move| possible_move.to() == self.cursor_cell);
-            match selected_move {
-                Some(_mv) => {
-                    return Some(ClientEvent::SendToServer(GameEvent::Move {
-                        mv: selected_move.unwrap().clone(), // TODO
-                        player_id: self.player_id,
-                    }));
-                }
-                None => {}
+                .filter(|mv| mv.path().get(step) == Some(&self.cursor_cell))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !continuing.is_empty() {
+                self.selected_path.push(self.cursor_cell);
+                self.possible_moves = continuing;
+            }
+
+            // a move is sent as soon as only one is left that ends on the selected cell
+            let selected_moves = self
+                .possible_moves
+                .iter()
+                .filter(|possible_move| possible_move.to() == self.cursor_cell)
+                .collect::<Vec<_>>();
+            if let [mv] = selected_moves[..] {
+                return Some(ClientEvent::SendToServer(GameEvent::Move {
+                    mv: mv.clone(),
+                    player_id: self.player_id,
+                }));
             }
         }
 
-        // selecting our own pawn
+        // selecting our own pawn, only if it is allowed to move
         if self.game_state.grid[self.cursor_cell].is_some_and(|x| x.player_id == self.player_id) {
+            let moves = legal_moves(&self.game_state.grid, self.player_id)
+                .into_iter()
+                .filter(|mv| mv.from() == self.cursor_cell)
+                .collect::<Vec<_>>();
+            if moves.is_empty() {
+                info!("❌ This pawn cannot move");
+                return None;
+            }
             self.selected_cell = Some(self.cursor_cell);
-            if let Some(selected_cell) = self.selected_cell
-                && let Some(player) = self.game_state.players.get(&self.player_id)
-            {
-                match get_possible_moves(&self.game_state.grid, selected_cell, player) {
-                    Ok(moves) => self.possible_moves = moves,
-                    Err(err) => info!("❌ Error while selecting own's pawn: {err}"),
+            self.selected_path.clear();
+            self.possible_moves = moves;
+        }
+        None
+    }
+    /// `d` offers a draw, `y` and `n` answer the opponent's offer
+    fn draw_offer(&mut self, key: char) -> Option<ClientEvent> {
+        let event = match (key, self.game_state.draw_offer) {
+            ('d', None) => GameEvent::DrawOffered {
+                player_id: self.player_id,
+            },
+            ('y' | 'n', Some(offering)) if offering != self.player_id => GameEvent::DrawAnswered {
+                player_id: self.player_id,
+                accepted: key == 'y',
+            },
+            _ => return None,
+        };
+        Some(ClientEvent::SendToServer(event))
+    }
+    /// `t` asks to take back the last move, `a` and `r` answer the opponent's request: keys of
+    /// their own, a draw offer being possibly pending at the same time
+    fn takeback_request(&mut self, key: char) -> Option<ClientEvent> {
+        let event = match (key, self.game_state.takeback_request) {
+            ('t', None) => GameEvent::TakebackRequested {
+                player_id: self.player_id,
+            },
+            ('a' | 'r', Some(requesting)) if requesting != self.player_id => {
+                GameEvent::TakebackAnswered {
+                    player_id: self.player_id,
+                    accepted: key == 'a',
                 }
-            } else {
-                info!("❌ Could now unwrap `selected_cell` or `player`");
             }
+            _ => return None,
+        };
+        Some(ClientEvent::SendToServer(event))
+    }
+    /// `h` asks the engine for a move in the background, or hides the one it suggested
+    fn toggle_hint(&mut self) {
+        if self.hint.take().is_some()
+            || self.hint_pending.is_some()
+            || self.game_state.is_turn != self.player_id
+        {
+            return;
+        }
+        let position = self.game_state.grid.hash();
+        self.hint_pending = Some(position);
+        let game_state = (*self.game_state).clone();
+        let events_tx = self.events_tx.clone();
+        thread::spawn(move || {
+            let mv = EngineKind::default()
+                .build_with(engine::default_book(), engine::default_tablebases())
+                .best_move(&game_state, Limits::time(HINT_TIME));
+            // nobody is listening any more if the client was closed meanwhile
+            let _ = events_tx.send(IncomingEvent::HintFound { position, mv });
+        });
+    }
+    /// shows the move found for `position`, unless the game went on in the meantime
+    pub fn handle_hint(&mut self, position: u64, mv: Option<Move>) {
+        if self.hint_pending != Some(position) {
+            return;
+        }
+        self.hint_pending = None;
+        if self.game_state.grid.hash() == position && self.game_state.is_turn == self.player_id {
+            if mv.is_some() {
+                self.hints_used += 1;
+            }
+            self.hint = mv;
         }
-        return None;
+    }
+    /// squares the selected piece can land on next, given the steps already picked
+    fn next_steps(&self) -> Vec<Coords> {
+        let step = self.selected_path.len() + 1;
+        self.possible_moves
+            .iter()
+            .filter_map(|mv| mv.path().get(step).copied())
+            .collect()
     }
 }
 
@@ -145,102 +290,89 @@ impl Widget for &GameScene {
         // info area - TODO: i wanna see first the client name
         let mut players_scoreboard = vec![];
         for player in self.game_state.players.iter() {
-            players_scoreboard.push(
-                player
-                    .1
-                    .pretty_print_scoreboard(
-                        self.game_state.is_turn,
-                        if player.1.id == self.player_id {
-                            Color::Green
-                        } else {
-                            Color::Red
-                        },
-                    )
-                    .left_aligned(),
+            let mut scoreboard = player.1.pretty_print_scoreboard(
+                self.game_state.is_turn,
+                if player.1.id == self.player_id {
+                    Color::Green
+                } else {
+                    Color::Red
+                },
             );
+            if player.1.id == self.player_id && self.hints_used > 0 {
+                scoreboard.push_span(" hints:".white());
+                scoreboard.push_span(format!(" {}", self.hints_used).white().bold());
+            }
+            players_scoreboard.push(scoreboard.left_aligned());
+        }
+        if let Some(hint) = &self.hint {
+            players_scoreboard.push(Line::from(vec![
+                "Hint: ".into(),
+                notation(&self.game_state.grid, hint).light_blue().bold(),
+                " hide ".into(),
+                "<H>".blue().bold(),
+            ]));
+        } else if self.hint_pending.is_some() {
+            players_scoreboard.push(Line::from("Looking for a hint...").gray());
+        }
+        match self.game_state.draw_offer {
+            Some(offering) if offering == self.player_id => {
+                players_scoreboard
+                    .push(Line::from("Draw offered, waiting for the opponent...").gray());
+            }
+            Some(_) => {
+                players_scoreboard.push(Line::from(vec![
+                    "The opponent offers a draw: accept ".into(),
+                    "<Y>".blue().bold(),
+                    " decline ".into(),
+                    "<N>".blue().bold(),
+                ]));
+            }
+            None => {}
+        }
+        match self.game_state.takeback_request {
+            Some(requesting) if requesting == self.player_id => {
+                players_scoreboard
+                    .push(Line::from("Takeback requested, waiting for the opponent...").gray());
+            }
+            Some(_) => {
+                players_scoreboard.push(Line::from(vec![
+                    "The opponent asks to take back their move: accept ".into(),
+                    "<A>".blue().bold(),
+                    " refuse ".into(),
+                    "<R>".blue().bold(),
+                ]));
+            }
+            None => {}
         }
         Paragraph::new(players_scoreboard).render(info_area, buf);
 
-        // board
-        let cell_size = board_area.height / 8;
-        let rows = Layout::vertical([Length(cell_size); 8])
-            .flex(ratatui::layout::Flex::Start)
-            .split(board_area);
-
-        let cells = rows
-            .iter()
-            .flat_map(|row| {
-                Layout::horizontal([Length(cell_size * 2); 8])
-                    .flex(ratatui::layout::Flex::Center)
-                    .split(*row)
-                    .iter()
-                    .copied()
-                    .take(8)
-                    .collect::<Vec<Rect>>()
-            })
-            .collect::<Vec<_>>();
-
-        for i in 0..CELL_N {
-            for j in 0..CELL_N {
-                let coords = Coords { x: i, y: j };
-                let c = &Circle {
-                    x: 5.0,
-                    y: 5.0,
-                    color: if self.game_state.grid[coords]
-                        .is_some_and(|x| x.player_id == self.player_id)
-                    {
-                        Color::Green // player
-                    } else {
-                        Color::Red // opponent
-                    },
-                    radius: 5.0,
-                };
-                let bg_color = if is_white(coords) {
-                    Color::White
-                } else {
-                    Color::Black
-                };
-
-                let border_color = if coords == self.cursor_cell {
+        render_board(
+            &self.game_state.grid,
+            self.player_id,
+            |coords| {
+                if coords == self.cursor_cell {
                     if self.game_state.is_turn == self.player_id {
-                        Color::LightGreen
+                        Some(Color::LightGreen)
                     } else {
-                        Color::Gray
+                        Some(Color::Gray)
                     }
-                } else if let Some(selected_cell) = self.selected_cell
-                    && coords == selected_cell
+                } else if self.selected_cell == Some(coords) || self.selected_path.contains(&coords)
                 {
-                    Color::Yellow
+                    Some(Color::Yellow)
+                } else if self.next_steps().contains(&coords) {
+                    Some(Color::LightYellow)
                 } else if self
-                    .possible_moves
-                    .iter()
-                    .map(|cell| cell.to())
-                    .collect::<Vec<_>>()
-                    .contains(&coords)
+                    .hint
+                    .as_ref()
+                    .is_some_and(|hint| hint.path().contains(&coords))
                 {
-                    Color::LightYellow
+                    Some(Color::LightBlue)
                 } else {
-                    bg_color
-                };
-
-                Canvas::default()
-                    .block(
-                        Block::bordered()
-                            .bg(bg_color)
-                            .fg(border_color)
-                            .border_type(BorderType::Double),
-                    )
-                    .marker(Marker::Braille)
-                    .background_color(bg_color)
-                    .x_bounds([0.0, 10.0])
-                    .y_bounds([0.0, 10.0])
-                    .paint(|ctx| {
-                        if self.game_state.grid[coords].is_some_and(|x| x.player_id > 0) {
-                            ctx.draw(c);
-                        }
-                    })
-                    .render(cells[coords_to_index(coords)], buf);
-            }
-        }
+                    None
+                }
+            },
+            board_area,
+            buf,
+        );
     }
 }
@@ -0,0 +1,24 @@
+//! The parts of the client that don't need a terminal: the connection to the server, and the
+//! events exchanged with it.
+pub mod network;
+
+use engine::analysis::PlyAnalysis;
+use store::{game_state::GameEvent, game_utils::Move, player::PlayerId};
+
+// cliewnt has to handle this; can come from server or input; is sent via thread channel
+pub enum IncomingEvent {
+    Input(crossterm::event::KeyEvent),
+    ServerMessage(GameEvent),
+    ClientIdCommunication(PlayerId),
+    // a move suggested by the engine, for the position with this hash
+    HintFound { position: u64, mv: Option<Move> },
+    // the analysis of a move of the game being reviewed
+    PlyAnalysed { ply: usize, analysis: PlyAnalysis },
+}
+
+// events that the client can send to the server
+pub enum ClientToServerMessage {
+    SendEvent(GameEvent),
+    /// leaves the server and stops the network thread
+    Disconnect,
+}
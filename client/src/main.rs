@@ -1,6 +1,7 @@
+mod analysis;
+mod board;
 mod game;
 mod main_menu;
-mod network;
 mod scene;
 use std::{
     io,
@@ -24,7 +25,9 @@ use store::{
     player::PlayerId,
 };
 
-use crate::{game::GameScene, main_menu::MainMenuScene, network::run_net_thread, scene::Scene};
+use client::{ClientToServerMessage, IncomingEvent, network::run_net_thread};
+
+use crate::{analysis::AnalysisScene, game::GameScene, main_menu::MainMenuScene, scene::Scene};
 
 #[derive(Debug)]
 pub struct App {
@@ -34,34 +37,25 @@ pub struct App {
     main_to_network_tx: Option<mpsc::Sender<ClientToServerMessage>>,
 }
 
-// cliewnt has to handle this; can come from server or input; is sent via thread channel
-pub enum IncomingEvent {
-    Input(crossterm::event::KeyEvent),
-    ServerMessage(GameEvent),
-    ClientIdCommunication(PlayerId),
-}
-
-// events that the client can send to the server
-pub enum ClientToServerMessage {
-    SendEvent(GameEvent),
-}
-
 fn handle_input_events(tx: mpsc::Sender<IncomingEvent>) {
     loop {
-        match crossterm::event::read().unwrap() {
-            crossterm::event::Event::Key(key_event) => {
-                tx.send(IncomingEvent::Input(key_event)).unwrap()
-            }
-            _ => {}
+        if let crossterm::event::Event::Key(key_event) = crossterm::event::read().unwrap() {
+            tx.send(IncomingEvent::Input(key_event)).unwrap()
         }
     }
 }
 
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl App {
     pub fn new() -> Self {
         Self {
             exit: false,
-            current_scene: Scene::Menu(MainMenuScene::new(None)),
+            current_scene: Scene::Menu(MainMenuScene::new(None, 0, None)),
             player_id: 0,
             main_to_network_tx: None,
         }
@@ -77,6 +71,12 @@ impl App {
             // matching events read from a thread channel, ie. coming from input handling, server messages, or internal inter-thread communication
             match rx.recv_timeout(Duration::from_millis(100)) {
                 Ok(IncomingEvent::ClientIdCommunication(client_id)) => self.player_id = client_id, // TODO lets find a better way
+                Ok(IncomingEvent::HintFound { position, mv }) => {
+                    self.current_scene.handle_hint(position, mv)
+                }
+                Ok(IncomingEvent::PlyAnalysed { ply, analysis }) => {
+                    self.current_scene.handle_analysis(ply, analysis)
+                }
                 // stuff triggered by input
                 Ok(IncomingEvent::Input(key_event)) => {
                     if key_event.kind == KeyEventKind::Press {
@@ -113,17 +113,27 @@ impl App {
                                         });
                                     }
                                     ClientEvent::SendToServer(game_event) => {
-                                        if let Some(tx) = &self.main_to_network_tx {
-                                            if tx
+                                        if let Some(tx) = &self.main_to_network_tx
+                                            && tx
                                                 .send(ClientToServerMessage::SendEvent(game_event))
                                                 .is_err()
-                                            {
-                                                info!(
-                                                    "❌ Something happened while sending event to server..."
-                                                )
-                                            }
+                                        {
+                                            info!(
+                                                "❌ Something happened while sending event to server..."
+                                            )
                                         }
                                     }
+                                    ClientEvent::AnalyseGame(game_state) => {
+                                        self.current_scene = Scene::Analysis(AnalysisScene::new(
+                                            *game_state,
+                                            self.player_id,
+                                            tx.clone(),
+                                        ))
+                                    }
+                                    ClientEvent::LeaveAnalysis => {
+                                        self.current_scene =
+                                            Scene::Menu(MainMenuScene::new(None, 0, None))
+                                    }
                                     _ => {}
                                 }
                             }
@@ -134,34 +144,47 @@ impl App {
                 Ok(IncomingEvent::ServerMessage(msg)) => {
                     if let Some(game_event) = self.current_scene.handle_event(msg) {
                         match game_event {
-                            ClientEvent::GoToGame(players, starting_player) => {
+                            ClientEvent::GoToGame(players, starting_player, variant) => {
                                 self.current_scene = Scene::Game(GameScene::new(
                                     players,
                                     self.player_id,
                                     starting_player,
+                                    variant,
+                                    tx.clone(),
                                 ))
                             }
+                            ClientEvent::ResumeGame(log) => {
+                                match GameScene::resume(&log, self.player_id, tx.clone()) {
+                                    Ok(game_scene) => self.current_scene = Scene::Game(game_scene),
+                                    Err(err) => info!("❌ Cannot resume the game: {err}"),
+                                }
+                            }
                             ClientEvent::GoToMenu(end_game_reason) => {
                                 // disconnect the net thread, delete channel, and go to menu
-                                if let Some(tx) = &self.main_to_network_tx {
-                                    if tx
-                                        .send(ClientToServerMessage::SendEvent(
-                                            GameEvent::EndGame {
-                                                reason: end_game_reason.clone(),
-                                            },
-                                        ))
-                                        .is_err()
-                                    {
-                                        info!("❌ Something happened while going to menu...")
-                                    }
+                                if let Some(tx) = &self.main_to_network_tx
+                                    && tx.send(ClientToServerMessage::Disconnect).is_err()
+                                {
+                                    info!("❌ Something happened while going to menu...")
                                 }
                                 self.main_to_network_tx = None;
 
-                                self.current_scene =
-                                    Scene::Menu(MainMenuScene::new(Some(end_game_reason.clone())));
+                                let (hints_used, game_state) = match &self.current_scene {
+                                    Scene::Game(game_scene) => (
+                                        game_scene.hints_used(),
+                                        Some(Box::new(game_scene.game_state().clone())),
+                                    ),
+                                    _ => (0, None),
+                                };
+                                self.current_scene = Scene::Menu(MainMenuScene::new(
+                                    Some(end_game_reason.clone()),
+                                    hints_used,
+                                    game_state,
+                                ));
                             }
                             ClientEvent::GoToLobby(_, _) => todo!(),
                             ClientEvent::SendToServer(_game_event) => todo!(),
+                            // reviewing a game is asked for from the keyboard, not by the server
+                            ClientEvent::AnalyseGame(_) | ClientEvent::LeaveAnalysis => {}
                         }
                     }
                 }
@@ -198,6 +221,12 @@ impl Widget for &App {
             "<Arrows>".blue().bold(),
             " Select ".into(),
             "<space>".blue().bold(),
+            " Offer draw ".into(),
+            "<D>".blue().bold(),
+            " Takeback ".into(),
+            "<T>".blue().bold(),
+            " Hint ".into(),
+            "<H>".blue().bold(),
             " Quit ".into(),
             "<Q>".red().bold(),
         ])
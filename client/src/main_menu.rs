@@ -3,12 +3,14 @@ use std::collections::HashMap;
 use crossterm::event::{Event, KeyCode, KeyEvent};
 use ratatui::{
     layout::{Constraint, Flex, Layout, Margin, Rect},
-    style::{Color, Style},
+    style::{Color, Style, Stylize},
+    text::{Line, Text},
     widgets::{Block, Borders, Clear, Paragraph, Widget},
 };
 use store::{
-    game_state::{ClientEvent, EndGameReason, GameEvent},
+    game_state::{ClientEvent, DrawKind, EndGameReason, GameEvent, GameState},
     player::{Player, PlayerId},
+    rules::Variant,
 };
 use tui_input::{Input, backend::crossterm::EventHandler};
 
@@ -20,7 +22,12 @@ pub struct MainMenuScene {
     addr_in: Input,
     focused: usize,
     num_players: usize,
+    variant: Variant,
     prev_end_game_reason: Option<EndGameReason>,
+    // hints asked for during the previous game
+    prev_hints_used: usize,
+    // the previous game, which can be analysed
+    prev_game: Option<Box<GameState>>,
 }
 
 impl Widget for &MainMenuScene {
@@ -95,16 +102,42 @@ impl Widget for &MainMenuScene {
             );
         } else if let Some(reason) = &self.prev_end_game_reason {
             let block = Block::bordered().title("Alert");
-            let popup_area = popup_area(area, 60, 20);
+            let popup_area = popup_area(area, 60, 30);
 
-            let simple = Paragraph::new(match reason {
+            let mut text = match reason {
                 EndGameReason::PlayerLeft { player_id } => {
                     format!("You won the previous game because {player_id} left the game!")
                 }
                 EndGameReason::PlayerWon { winner } => {
-                    format!("Player {winner} won the previous game!") // TODO: make this happen in Game and show username and score
+                    format!("Player {winner} won the previous game!") // TODO: show username and score
                 }
-            });
+                EndGameReason::Draw { kind } => match kind {
+                    DrawKind::MoveLimit => {
+                        "The previous game was drawn: too many moves without progress.".to_string()
+                    }
+                    DrawKind::Repetition => {
+                        "The previous game was drawn by threefold repetition.".to_string()
+                    }
+                    DrawKind::Agreement => "The previous game was drawn by agreement.".to_string(),
+                    DrawKind::Adjudication => {
+                        "The previous game was adjudicated a draw: neither player could win."
+                            .to_string()
+                    }
+                },
+            };
+            match self.prev_hints_used {
+                0 => {}
+                1 => text.push_str("\nYou used 1 hint."),
+                hints => text.push_str(&format!("\nYou used {hints} hints.")),
+            }
+            let mut text = Text::from(text);
+            if self.can_analyse() {
+                text.push_line(Line::from(vec![
+                    "Review it with the engine ".into(),
+                    "<Tab>".blue().bold(),
+                ]));
+            }
+            let simple = Paragraph::new(text);
             Clear.render(popup_area, buf);
             block.render(popup_area, buf);
             simple.render(
@@ -119,17 +152,32 @@ impl Widget for &MainMenuScene {
 }
 
 impl MainMenuScene {
-    pub fn new(prev_end_game_reason: Option<EndGameReason>) -> Self {
+    pub fn new(
+        prev_end_game_reason: Option<EndGameReason>,
+        prev_hints_used: usize,
+        prev_game: Option<Box<GameState>>,
+    ) -> Self {
         Self {
             submit: false,
             username_in: Input::default().with_value("".into()),
             addr_in: Input::default().with_value("127.0.0.1:5000".into()),
             focused: 0,
             num_players: 0,
+            variant: Variant::default(),
             players: HashMap::new(),
-            prev_end_game_reason: prev_end_game_reason,
+            prev_end_game_reason,
+            prev_hints_used,
+            prev_game,
         }
     }
+    /// whether a previous game with moves to review can be analysed, before connecting again
+    fn can_analyse(&self) -> bool {
+        !self.submit
+            && self
+                .prev_game
+                .as_ref()
+                .is_some_and(|game_state| game_state.moves().next().is_some())
+    }
     fn can_submit(&self) -> bool {
         !self.username_in.value().is_empty() && !self.addr_in.value().is_empty()
     }
@@ -138,11 +186,18 @@ impl MainMenuScene {
         match game_event {
             GameEvent::PlayerJoined { player } => {
                 self.players.insert(player.id, player);
-                return None;
+                None
             }
-            GameEvent::TurnChanged { player_id } => {
-                Some(ClientEvent::GoToGame(self.players.clone(), player_id))
+            GameEvent::VariantAnnounced { variant } => {
+                self.variant = variant;
+                None
             }
+            GameEvent::TurnChanged { player_id } => Some(ClientEvent::GoToGame(
+                self.players.clone(),
+                player_id,
+                self.variant,
+            )),
+            GameEvent::GameResumed { log } => Some(ClientEvent::ResumeGame(log)),
             _ => None,
         }
     }
@@ -157,31 +212,34 @@ impl MainMenuScene {
                         String::from(self.addr_in.value()),
                     ))
                 } else {
-                    return None;
+                    None
                 }
             }
+            KeyCode::Tab if self.can_analyse() => {
+                self.prev_game.take().map(ClientEvent::AnalyseGame)
+            }
             KeyCode::Down => {
                 if self.focused < 2 {
                     self.focused += 1
                 }
-                return None;
+                None
             }
             KeyCode::Up => {
                 if self.focused > 0 {
                     self.focused -= 1
                 }
-                return None;
+                None
             }
             _ => match self.focused {
                 0 => {
                     self.username_in.handle_event(&Event::Key(key_event));
-                    return None;
+                    None
                 }
                 1 => {
                     self.addr_in.handle_event(&Event::Key(key_event));
-                    return None;
+                    None
                 }
-                _ => return None,
+                _ => None,
             },
         }
     }
@@ -50,7 +50,7 @@ pub fn run_net_thread(
     let auth = ClientAuthentication::Unsecure {
         client_id,
         protocol_id: PROTOCOL_ID,
-        server_addr: server_addr,
+        server_addr,
         user_data: Some(to_netcode_user_data(username)),
     };
 
@@ -64,7 +64,7 @@ pub fn run_net_thread(
 
     let mut last_updated = Instant::now();
 
-    loop {
+    'net: loop {
         let now = Instant::now();
         let duration = now - last_updated;
         last_updated = now;
@@ -78,17 +78,26 @@ pub fn run_net_thread(
         // get Move instruction from input thread and send to server
         while let Ok(command) = main_to_network_rx.try_recv() {
             match command {
+                ClientToServerMessage::Disconnect => {
+                    info!("Game ended, exiting network thread...");
+                    transport.disconnect();
+                    break 'net;
+                }
                 ClientToServerMessage::SendEvent(game_event) => match game_event {
-                    GameEvent::EndGame { .. } => {
-                        info!("Game ended, exiting network thread...");
-                        break;
-                    }
-                    GameEvent::Move { .. } => match postcard::to_allocvec(&game_event) {
-                        Ok(bytes) => client.send_message(DefaultChannel::ReliableOrdered, bytes),
-                        Err(_) => {
-                            info!("Error while serializing game event")
+                    GameEvent::Move { .. }
+                    | GameEvent::DrawOffered { .. }
+                    | GameEvent::DrawAnswered { .. }
+                    | GameEvent::TakebackRequested { .. }
+                    | GameEvent::TakebackAnswered { .. } => {
+                        match postcard::to_allocvec(&game_event) {
+                            Ok(bytes) => {
+                                client.send_message(DefaultChannel::ReliableOrdered, bytes)
+                            }
+                            Err(_) => {
+                                info!("Error while serializing game event")
+                            }
                         }
-                    },
+                    }
                     _ => {}
                 },
             }
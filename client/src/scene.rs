@@ -1,14 +1,19 @@
 use crossterm::event::KeyEvent;
+use engine::analysis::PlyAnalysis;
 use ratatui::prelude::Widget;
 use ratatui::{buffer::Buffer, layout::Rect};
-use store::game_state::{ClientEvent, GameEvent};
+use store::{
+    game_state::{ClientEvent, GameEvent},
+    game_utils::Move,
+};
 
-use crate::{game::GameScene, main_menu::MainMenuScene};
+use crate::{analysis::AnalysisScene, game::GameScene, main_menu::MainMenuScene};
 
 #[derive(Debug)]
 pub enum Scene {
     Menu(MainMenuScene),
     Game(GameScene),
+    Analysis(AnalysisScene),
 }
 
 impl Scene {
@@ -16,18 +21,31 @@ impl Scene {
         match self {
             Scene::Menu(menu) => menu.handle_input(key_event),
             Scene::Game(game_scene) => game_scene.handle_input(key_event),
+            Scene::Analysis(analysis_scene) => analysis_scene.handle_input(key_event),
         }
     }
     pub fn handle_event(&mut self, game_event: GameEvent) -> Option<ClientEvent> {
         match self {
             Scene::Menu(menu) => menu.handle_server_events(game_event),
             Scene::Game(game_scene) => game_scene.handle_server_events(game_event),
+            Scene::Analysis(_) => None,
+        }
+    }
+    pub fn handle_hint(&mut self, position: u64, mv: Option<Move>) {
+        if let Scene::Game(game_scene) = self {
+            game_scene.handle_hint(position, mv);
+        }
+    }
+    pub fn handle_analysis(&mut self, ply: usize, analysis: PlyAnalysis) {
+        if let Scene::Analysis(analysis_scene) = self {
+            analysis_scene.handle_analysis(ply, analysis);
         }
     }
     pub fn handle_render(&self, area: Rect, buf: &mut Buffer) {
         match self {
             Scene::Menu(main_menu_scene) => main_menu_scene.render(area, buf),
             Scene::Game(game_scene) => game_scene.render(area, buf),
+            Scene::Analysis(analysis_scene) => analysis_scene.render(area, buf),
         }
     }
 }
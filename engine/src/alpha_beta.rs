@@ -0,0 +1,707 @@
+//! Negamax search with alpha-beta pruning, extended by a quiescence search over captures so
+//! that positions are never evaluated in the middle of an exchange. Positions already searched
+//! deep enough are looked up in a transposition table instead of being searched again.
+//!
+//! The search deepens one move at a time, within an aspiration window around the score of the
+//! previous iteration, and tries the moves most likely to cause a cutoff first: the best move
+//! found for the position by earlier iterations, then captures, then killer moves.
+//! Given endgame tablebases, positions with few enough pieces are scored from them instead,
+//! and given an opening book, the positions in it are played from it without searching.
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use store::{
+    board::Board,
+    game_state::GameState,
+    game_utils::{Move, legal_moves},
+    opening_book::OpeningBook,
+    pdn::notation,
+    player::PlayerId,
+    tablebase::{Outcome, Tablebases},
+    transposition::TranspositionTable,
+    zobrist::position_hash,
+};
+
+use crate::{Engine, Limits, eval::evaluate, time::TimeManager};
+
+/// the score of a won position, minus the moves needed to win it
+pub const MATE: i32 = 100_000;
+/// above any score
+const INFINITY: i32 = MATE + 1;
+/// how deep to search when no limit is given
+pub const DEFAULT_DEPTH: usize = 8;
+// nodes searched between two looks at the clock
+const CLOCK_CHECK: u64 = 1024;
+/// slots in the transposition table by default, a few megabytes
+pub const TABLE_ENTRIES: usize = 1 << 16;
+/// half the width of the window searched around the previous score, a third of a pawn
+const ASPIRATION_WINDOW: i32 = 33;
+/// the first depth searched with an aspiration window, the scores of shallower ones being too
+/// unstable
+const ASPIRATION_DEPTH: usize = 4;
+
+/// how a stored score relates to the real one, depending on the cutoffs during the search
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    /// the search failed high: the score is at least this
+    Lower,
+    /// the search failed low: the score is at most this
+    Upper,
+}
+#[derive(Debug, Clone)]
+struct Stored {
+    score: i32,
+    bound: Bound,
+    /// the move that scored best or caused the cutoff, tried first next time
+    best: Option<Move>,
+}
+
+/// the outcome of one iteration of the search
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub depth: usize,
+    /// from the point of view of the player to move
+    pub score: i32,
+    /// positions visited since the search started
+    pub nodes: u64,
+    pub elapsed: Duration,
+    /// the moves both players are expected to play, starting with the best one
+    pub pv: Vec<Move>,
+}
+
+impl Report {
+    /// the principal variation in PDN notation, `board` being the position searched
+    pub fn pv_notation(&self, board: &Board) -> String {
+        self.pv
+            .iter()
+            .map(|mv| notation(board, mv))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// called with the report of each iteration
+pub type Reporter = Box<dyn FnMut(&Report) + Send>;
+
+pub struct AlphaBeta {
+    /// positions visited by the last search
+    pub nodes: u64,
+    deadline: Option<Instant>,
+    stopped: bool,
+    table: TranspositionTable<Stored>,
+    /// per ply, the last two quiet moves that caused a cutoff
+    killers: Vec<[Option<Move>; 2]>,
+    reporter: Option<Reporter>,
+    /// perfect play once few enough pieces are left
+    tablebases: Option<Arc<Tablebases>>,
+    /// moves played without searching in the first moves of the game
+    book: Option<Arc<OpeningBook>>,
+}
+impl Default for AlphaBeta {
+    fn default() -> Self {
+        Self::with_table_entries(TABLE_ENTRIES)
+    }
+}
+impl AlphaBeta {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_table_entries(entries: usize) -> Self {
+        Self {
+            nodes: 0,
+            deadline: None,
+            stopped: false,
+            table: TranspositionTable::new(entries),
+            killers: Vec::new(),
+            reporter: None,
+            tablebases: None,
+            book: None,
+        }
+    }
+    pub fn with_tablebases(mut self, tablebases: Arc<Tablebases>) -> Self {
+        self.tablebases = Some(tablebases);
+        self
+    }
+    pub fn with_book(mut self, book: Arc<OpeningBook>) -> Self {
+        self.book = Some(book);
+        self
+    }
+    /// calls `reporter` after each iteration of `best_move`
+    pub fn on_report(mut self, reporter: impl FnMut(&Report) + Send + 'static) -> Self {
+        self.reporter = Some(Box::new(reporter));
+        self
+    }
+
+    /// the best move for `player_id` searching `depth` moves ahead, with its score; `None`
+    /// if the player can't move or the time ran out
+    pub fn search(
+        &mut self,
+        board: &Board,
+        player_id: PlayerId,
+        depth: usize,
+    ) -> Option<(Move, i32)> {
+        self.search_window(board, player_id, depth, -INFINITY, INFINITY)
+    }
+
+    /// the best move for `player_id` and its score, deepening one move at a time up to `depth`
+    /// without a book, tablebase moves or a time limit; `None` if the player can't move
+    pub fn search_deepening(
+        &mut self,
+        board: &Board,
+        player_id: PlayerId,
+        depth: usize,
+    ) -> Option<(Move, i32)> {
+        self.nodes = 0;
+        self.stopped = false;
+        self.deadline = None;
+        self.killers.clear();
+        let mut best: Option<(Move, i32)> = None;
+        for depth in 1..=depth {
+            let previous = best.as_ref().map_or(0, |(_, score)| *score);
+            best = self.aspiration_search(board, player_id, depth, previous);
+            if best
+                .as_ref()
+                .is_none_or(|(_, score)| score.abs() >= MATE - depth as i32)
+            {
+                break;
+            }
+        }
+        best
+    }
+
+    /// searches the root within `(alpha, beta)`: a score at or below `alpha` is only an upper
+    /// bound, and one at or above `beta` a lower bound
+    fn search_window(
+        &mut self,
+        board: &Board,
+        player_id: PlayerId,
+        depth: usize,
+        alpha: i32,
+        beta: i32,
+    ) -> Option<(Move, i32)> {
+        let mut board = board.clone();
+        let hash = position_hash(&board, player_id);
+        let opponent = opponent(&board, player_id);
+        let mut best: Option<(Move, i32)> = None;
+        for mv in self.ordered_moves(&board, player_id, 0, hash) {
+            let floor = best.as_ref().map_or(alpha, |(_, score)| alpha.max(*score));
+            let record = board.play(&mv).unwrap();
+            let score = -self.negamax(
+                &mut board,
+                opponent,
+                depth.saturating_sub(1),
+                1,
+                -beta,
+                -floor,
+            );
+            board.undo(&record).unwrap();
+            if self.stopped {
+                return None;
+            }
+            if best.as_ref().is_none_or(|(_, best)| score > *best) {
+                best = Some((mv, score));
+            }
+            if score >= beta {
+                break;
+            }
+        }
+
+        let (mv, score) = best?;
+        let bound = if score >= beta {
+            Bound::Lower
+        } else if score <= alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        let stored = Stored {
+            score: to_table(score, 0),
+            bound,
+            best: Some(mv.clone()),
+        };
+        self.table.insert(hash, depth, stored);
+        Some((mv, score))
+    }
+
+    /// searches a narrow window around the previous score first, which cuts off more, and
+    /// searches again with the window open on the side the score fell outside of
+    fn aspiration_search(
+        &mut self,
+        board: &Board,
+        player_id: PlayerId,
+        depth: usize,
+        previous: i32,
+    ) -> Option<(Move, i32)> {
+        let (mut alpha, mut beta) = if depth >= ASPIRATION_DEPTH {
+            (previous - ASPIRATION_WINDOW, previous + ASPIRATION_WINDOW)
+        } else {
+            (-INFINITY, INFINITY)
+        };
+        loop {
+            let (mv, score) = self.search_window(board, player_id, depth, alpha, beta)?;
+            if score <= alpha {
+                alpha = -INFINITY;
+            } else if score >= beta {
+                beta = INFINITY;
+            } else {
+                return Some((mv, score));
+            }
+        }
+    }
+
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        player_id: PlayerId,
+        depth: usize,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if depth == 0 {
+            return self.quiescence(board, player_id, ply, alpha, beta);
+        }
+        if self.out_of_time() {
+            return 0;
+        }
+        if let Some(outcome) = self.probe(board, player_id) {
+            return tablebase_score(outcome, ply);
+        }
+        let hash = position_hash(board, player_id);
+        if let Some(entry) = self.table.get(hash)
+            && entry.depth >= depth
+        {
+            let score = from_table(entry.value.score, ply);
+            match entry.value.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => {}
+            }
+        }
+
+        let moves = self.ordered_moves(board, player_id, ply as usize, hash);
+        if moves.is_empty() {
+            return -MATE + ply;
+        }
+        let original_alpha = alpha;
+        let opponent = opponent(board, player_id);
+        let mut best = None;
+        for mv in moves {
+            let record = board.play(&mv).unwrap();
+            let score = -self.negamax(board, opponent, depth - 1, ply + 1, -beta, -alpha);
+            board.undo(&record).unwrap();
+            if self.stopped {
+                return 0;
+            }
+            if score > alpha {
+                alpha = score;
+                best = Some(mv);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if alpha >= beta {
+            if let Some(mv) = &best
+                && !mv.is_capture()
+            {
+                self.add_killer(ply as usize, mv);
+            }
+            Bound::Lower
+        } else if alpha == original_alpha {
+            Bound::Upper
+        } else {
+            Bound::Exact
+        };
+        let score = to_table(alpha, ply);
+        self.table
+            .insert(hash, depth, Stored { score, bound, best });
+        alpha
+    }
+
+    /// searches captures until the position is quiet; since capturing is mandatory, there is
+    /// no standing pat while a capture is available
+    fn quiescence(
+        &mut self,
+        board: &mut Board,
+        player_id: PlayerId,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        if self.out_of_time() {
+            return 0;
+        }
+        let mut moves = legal_moves(board, player_id);
+        if moves.is_empty() {
+            return -MATE + ply;
+        }
+        if !moves[0].is_capture() {
+            return evaluate(board, player_id);
+        }
+        moves.sort_by_key(|mv| std::cmp::Reverse(mv.eaten().len()));
+        let opponent = opponent(board, player_id);
+        for mv in moves {
+            let record = board.play(&mv).unwrap();
+            let score = -self.quiescence(board, opponent, ply + 1, -beta, -alpha);
+            board.undo(&record).unwrap();
+            if self.stopped {
+                return 0;
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        alpha
+    }
+
+    /// the legal moves, in the order most likely to cause a cutoff: the best move stored for the
+    /// position, the captures taking the most pieces, the killer moves, and then the rest
+    fn ordered_moves(
+        &self,
+        board: &Board,
+        player_id: PlayerId,
+        ply: usize,
+        hash: u64,
+    ) -> Vec<Move> {
+        let stored = self
+            .table
+            .get(hash)
+            .and_then(|entry| entry.value.best.as_ref());
+        let killers = self.killers.get(ply);
+        let mut moves = legal_moves(board, player_id);
+        moves.sort_by_cached_key(|mv| {
+            let rank = if stored == Some(mv) {
+                0
+            } else if mv.is_capture() {
+                1
+            } else if killers.is_some_and(|killers| killers.contains(&Some(mv.clone()))) {
+                2
+            } else {
+                3
+            };
+            (rank, std::cmp::Reverse(mv.eaten().len()))
+        });
+        moves
+    }
+
+    fn add_killer(&mut self, ply: usize, mv: &Move) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None, None]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0].as_ref() != Some(mv) {
+            killers[1] = killers[0].replace(mv.clone());
+        }
+    }
+
+    /// follows the best moves stored in the table from the root, as long as they are legal
+    fn principal_variation(&self, board: &Board, player_id: PlayerId, depth: usize) -> Vec<Move> {
+        let mut board = board.clone();
+        let mut player_id = player_id;
+        let mut pv = Vec::new();
+        while pv.len() < depth {
+            let Some(mv) = self
+                .table
+                .get(position_hash(&board, player_id))
+                .and_then(|entry| entry.value.best.clone())
+            else {
+                break;
+            };
+            if !legal_moves(&board, player_id).contains(&mv) {
+                break;
+            }
+            board.apply(&mv).unwrap();
+            pv.push(mv);
+            player_id = opponent(&board, player_id);
+        }
+        pv
+    }
+
+    fn probe(&self, board: &Board, player_id: PlayerId) -> Option<Outcome> {
+        self.tablebases.as_ref()?.probe_board(board, player_id)
+    }
+
+    /// the move the tablebases pick: the fastest win, a draw, or else the slowest loss
+    fn tablebase_move(&self, board: &Board, player_id: PlayerId) -> Option<Move> {
+        self.probe(board, player_id)?;
+        let opponent = opponent(board, player_id);
+        let mut best: Option<(Move, i32)> = None;
+        for mv in legal_moves(board, player_id) {
+            let mut child = board.clone();
+            child.apply(&mv).unwrap();
+            let score = -tablebase_score(self.probe(&child, opponent)?, 1);
+            if best.as_ref().is_none_or(|(_, best)| score > *best) {
+                best = Some((mv, score));
+            }
+        }
+        best.map(|(mv, _)| mv)
+    }
+
+    fn out_of_time(&mut self) -> bool {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(CLOCK_CHECK)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            self.stopped = true;
+        }
+        self.stopped
+    }
+}
+
+impl Engine for AlphaBeta {
+    /// searches one move deeper at a time, until a limit is reached, and plays the best move of
+    /// the deepest search completed
+    fn best_move(&mut self, state: &GameState, limits: Limits) -> Option<Move> {
+        let board = &state.grid;
+        let player_id = state.is_turn;
+        let mut moves = legal_moves(board, player_id);
+        if moves.len() <= 1 {
+            return moves.pop();
+        }
+
+        if let Some(mv) = self
+            .book
+            .as_ref()
+            .and_then(|book| book.pick(board, player_id))
+        {
+            return Some(mv);
+        }
+        if let Some(mv) = self.tablebase_move(board, player_id) {
+            return Some(mv);
+        }
+
+        let start = Instant::now();
+        let time = TimeManager::new(&limits, state.moves().count() / 2);
+        self.nodes = 0;
+        self.stopped = false;
+        self.deadline = time.map(|time| time.deadline());
+        self.killers.clear();
+        let max_depth = match limits.depth {
+            None if limits.is_timed() => usize::MAX,
+            depth => depth.unwrap_or(DEFAULT_DEPTH),
+        };
+
+        let mut best = moves.swap_remove(0);
+        let mut previous = 0;
+        for depth in 1..=max_depth {
+            let Some((mv, score)) = self.aspiration_search(board, player_id, depth, previous)
+            else {
+                break;
+            };
+            best = mv;
+            previous = score;
+            if let Some(mut reporter) = self.reporter.take() {
+                reporter(&Report {
+                    depth,
+                    score,
+                    nodes: self.nodes,
+                    elapsed: start.elapsed(),
+                    pv: self.principal_variation(board, player_id, depth),
+                });
+                self.reporter = Some(reporter);
+            }
+            // no need to look further once the game is decided
+            if score.abs() >= MATE - depth as i32 {
+                break;
+            }
+            if time.is_some_and(|time| !time.can_start_iteration()) {
+                break;
+            }
+        }
+        Some(best)
+    }
+}
+
+// mate scores depend on how far the position is from the root, so the table keeps them as
+// distances from the position itself
+fn to_table(score: i32, ply: i32) -> i32 {
+    if score > MATE / 2 {
+        score + ply
+    } else if score < -MATE / 2 {
+        score - ply
+    } else {
+        score
+    }
+}
+fn from_table(score: i32, ply: i32) -> i32 {
+    if score > MATE / 2 {
+        score - ply
+    } else if score < -MATE / 2 {
+        score + ply
+    } else {
+        score
+    }
+}
+
+/// a tablebase outcome as a search score, wins and losses being scored like mates
+fn tablebase_score(outcome: Outcome, ply: i32) -> i32 {
+    match outcome {
+        Outcome::Win(plies) => MATE - ply - plies as i32,
+        Outcome::Loss(plies) => -MATE + ply + plies as i32,
+        Outcome::Draw => 0,
+    }
+}
+
+fn opponent(board: &Board, player_id: PlayerId) -> PlayerId {
+    let [bottom, top] = board.players();
+    if player_id == bottom { top } else { bottom }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::OnceLock};
+
+    use store::{player::Player, rules::Variant};
+
+    use super::*;
+
+    const WHITE: PlayerId = 1;
+    const BLACK: PlayerId = 2;
+
+    fn position(fen: &str) -> Board {
+        let (board, turn) = Board::from_fen(fen, Variant::American, [WHITE, BLACK]).unwrap();
+        assert_eq!(turn, WHITE);
+        board
+    }
+    fn game(fen: &str) -> GameState {
+        let players = [(WHITE, 1), (BLACK, -1)]
+            .map(|(id, direction)| {
+                let name = id.to_string();
+                let player = Player {
+                    id,
+                    name,
+                    direction,
+                    score: 0,
+                };
+                (id, player)
+            })
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        GameState::from_position(players, position(fen), WHITE)
+    }
+    // kings only, the pawns taking too long to solve in debug builds
+    fn tablebases() -> Arc<Tablebases> {
+        static TABLEBASES: OnceLock<Arc<Tablebases>> = OnceLock::new();
+        TABLEBASES
+            .get_or_init(|| Arc::new(Tablebases::generate_kings(3).unwrap()))
+            .clone()
+    }
+
+    #[test]
+    fn finds_a_win_in_one() {
+        let board = position("W:W23:B18");
+        let (mv, score) = AlphaBeta::new().search(&board, WHITE, 4).unwrap();
+        assert_eq!(notation(&board, &mv), "23x14");
+        assert_eq!(score, MATE - 1);
+    }
+
+    #[test]
+    fn finds_a_win_in_two() {
+        let mut board = position("W:WK14,K18:B5");
+        let (mv, score) = AlphaBeta::new().search(&board, WHITE, 4).unwrap();
+        assert_eq!(score, MATE - 3);
+        // whatever black answers, white takes the last piece
+        board.apply(&mv).unwrap();
+        for reply in legal_moves(&board, BLACK) {
+            let mut board = board.clone();
+            board.apply(&reply).unwrap();
+            let (_, score) = AlphaBeta::new().search(&board, WHITE, 1).unwrap();
+            assert_eq!(score, MATE - 1, "after {}", notation(&board, &reply));
+        }
+        // without enough depth to see it, the win is missed
+        let (_, score) = AlphaBeta::new()
+            .search(&position("W:WK14,K18:B5"), WHITE, 1)
+            .unwrap();
+        assert!(score < MATE / 2);
+    }
+
+    #[test]
+    fn prefers_the_capture_taking_more_pieces() {
+        let board = position("W:W30,21:B26,19,17,1");
+        for depth in 1..=5 {
+            let (mv, _) = AlphaBeta::new().search(&board, WHITE, depth).unwrap();
+            assert_eq!(notation(&board, &mv), "30x23x16", "depth {depth}");
+        }
+    }
+
+    #[test]
+    fn deepening_scores_like_a_fixed_depth_search() {
+        let starting = Board::starting_position(Variant::American, [WHITE, BLACK]);
+        let boards = [
+            starting,
+            position("W:W30,21:B26,19,17,1"),
+            position("W:WK10,27:B1"),
+            position("W:W21,22,23,25,26,30,31,32:B1,3,6,7,9,10,14"),
+        ];
+        // the aspiration windows only change how fast the score is found, not the score
+        for board in boards {
+            for depth in 1..=6 {
+                let fixed = AlphaBeta::new().search(&board, WHITE, depth).unwrap();
+                let deepening = AlphaBeta::new()
+                    .search_deepening(&board, WHITE, depth)
+                    .unwrap();
+                assert_eq!(deepening.1, fixed.1, "depth {depth}");
+            }
+        }
+    }
+
+    #[test]
+    fn mate_scores_are_stored_relative_to_the_position() {
+        for score in [MATE - 3, -MATE + 5, 120, -40] {
+            assert_eq!(from_table(to_table(score, 4), 4), score);
+        }
+        assert_eq!(to_table(MATE - 3, 2), MATE - 1);
+    }
+
+    #[test]
+    fn plays_the_fastest_win_from_the_tablebases() {
+        let tablebases = tablebases();
+        let state = game("W:WK14,K18:BK1");
+        let plies_to_win = |mv: &Move| {
+            let mut child = state.grid.clone();
+            child.apply(mv).unwrap();
+            match tablebases.probe_board(&child, BLACK) {
+                Some(Outcome::Loss(plies)) => plies,
+                _ => usize::MAX,
+            }
+        };
+        let plies = legal_moves(&state.grid, WHITE)
+            .iter()
+            .map(plies_to_win)
+            .collect::<Vec<_>>();
+        let fastest = *plies.iter().min().unwrap();
+        // some moves win slower, or not at all
+        assert!(plies.iter().any(|&plies| plies > fastest));
+
+        let mv = AlphaBeta::new()
+            .with_tablebases(tablebases.clone())
+            .best_move(&state, Limits::depth(1))
+            .unwrap();
+        assert_eq!(plies_to_win(&mv), fastest);
+    }
+
+    #[test]
+    fn the_search_stops_at_tablebase_positions() {
+        let board = position("W:WK14,K18:BK1");
+        let moves = legal_moves(&board, WHITE).len() as u64;
+
+        let mut engine = AlphaBeta::new().with_tablebases(tablebases());
+        let (_, score) = engine.search(&board, WHITE, 6).unwrap();
+        // every child is probed instead of searched
+        assert_eq!(engine.nodes, moves);
+        assert!(score > MATE - 100, "{score}");
+
+        let mut engine = AlphaBeta::new();
+        engine.search(&board, WHITE, 6).unwrap();
+        assert!(engine.nodes > moves);
+    }
+}
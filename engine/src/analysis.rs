@@ -0,0 +1,162 @@
+//! Reviewing a finished game: every position reached is searched for its best move, and each
+//! move played is judged by how much worse it scores than that one.
+use std::fmt;
+
+use store::{board::Board, game_state::GameState, game_utils::Move, player::PlayerId};
+
+use crate::{
+    alpha_beta::{AlphaBeta, MATE},
+    default_tablebases,
+};
+
+/// how deep each position is searched
+pub const ANALYSIS_DEPTH: usize = 8;
+/// scores beyond this, ten pawns, are all as good as won: moves aren't judged by the difference
+pub const DECISIVE: i32 = 1000;
+
+/// score lost by a move, in hundredths of a pawn, from which it is an inaccuracy
+pub const INACCURACY: i32 = 40;
+/// score lost from which it is a mistake
+pub const MISTAKE: i32 = 100;
+/// score lost from which it is a blunder
+pub const BLUNDER: i32 = 250;
+
+/// how bad a move is, compared to the best one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+impl Judgement {
+    /// the judgement of a move losing `loss` compared to the best one, `None` if it is good
+    pub fn of(loss: i32) -> Option<Judgement> {
+        match loss {
+            BLUNDER.. => Some(Judgement::Blunder),
+            MISTAKE.. => Some(Judgement::Mistake),
+            INACCURACY.. => Some(Judgement::Inaccuracy),
+            _ => None,
+        }
+    }
+}
+impl fmt::Display for Judgement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Judgement::Inaccuracy => write!(f, "inaccuracy"),
+            Judgement::Mistake => write!(f, "mistake"),
+            Judgement::Blunder => write!(f, "blunder"),
+        }
+    }
+}
+
+/// a move of the game, with the scores of the positions before and after it for the player
+/// who made it
+#[derive(Debug, Clone)]
+pub struct PlyAnalysis {
+    pub mv: Move,
+    pub player_id: PlayerId,
+    /// the position before the move, the best move being played
+    pub score: i32,
+    /// the position after the move
+    pub score_after: i32,
+    /// the move the engine would have played
+    pub best: Move,
+    pub judgement: Option<Judgement>,
+}
+impl PlyAnalysis {
+    /// how much worse the move played scores than the best one
+    pub fn loss(&self) -> i32 {
+        (self.score.clamp(-DECISIVE, DECISIVE) - self.score_after.clamp(-DECISIVE, DECISIVE)).max(0)
+    }
+}
+
+/// the analysis of each move of a game, in order, searched as they are asked for
+pub struct GameAnalysis {
+    engine: AlphaBeta,
+    depth: usize,
+    /// every position of the game with the player to move, the final one included
+    positions: Vec<(Board, PlayerId)>,
+    moves: Vec<Move>,
+    next: usize,
+    /// the best move and score of the position before the next move, when already searched
+    searched: Option<(Option<Move>, i32)>,
+}
+
+impl GameAnalysis {
+    /// an analysis of the moves leading to the current position of `state`, taken back ones
+    /// excluded
+    pub fn new(state: &GameState, depth: usize) -> GameAnalysis {
+        let (board, mut turn) = state.initial_position();
+        let mut board = board.clone();
+        let mut positions = vec![(board.clone(), turn)];
+        let moves = state.moves().cloned().collect::<Vec<_>>();
+        for mv in &moves {
+            board.apply(mv).unwrap();
+            turn = opponent(&board, turn);
+            positions.push((board.clone(), turn));
+        }
+        let engine = match default_tablebases() {
+            Some(tablebases) => AlphaBeta::new().with_tablebases(tablebases),
+            None => AlphaBeta::new(),
+        };
+        GameAnalysis {
+            engine,
+            depth,
+            positions,
+            moves,
+            next: 0,
+            searched: None,
+        }
+    }
+
+    /// the best move of a position and its score, a player who can't move having lost
+    fn search(&mut self, position: usize) -> (Option<Move>, i32) {
+        let (board, player_id) = &self.positions[position];
+        match self.engine.search_deepening(board, *player_id, self.depth) {
+            Some((mv, score)) => (Some(mv), score),
+            None => (None, -MATE),
+        }
+    }
+}
+
+impl Iterator for GameAnalysis {
+    type Item = PlyAnalysis;
+
+    fn next(&mut self) -> Option<PlyAnalysis> {
+        let mv = self.moves.get(self.next)?.clone();
+        let player_id = self.positions[self.next].1;
+        let (best, score) = match self.searched.take() {
+            Some(searched) => searched,
+            None => self.search(self.next),
+        };
+        let after = self.search(self.next + 1);
+        let score_after = -after.1;
+        self.searched = Some(after);
+        self.next += 1;
+
+        // a move was played, so there was one to find
+        let best = best.unwrap();
+        let mut analysis = PlyAnalysis {
+            judgement: None,
+            mv,
+            player_id,
+            score,
+            score_after,
+            best,
+        };
+        if analysis.mv != analysis.best {
+            analysis.judgement = Judgement::of(analysis.loss());
+        }
+        Some(analysis)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.moves.len() - self.next;
+        (left, Some(left))
+    }
+}
+
+fn opponent(board: &Board, player_id: PlayerId) -> PlayerId {
+    let [bottom, top] = board.players();
+    if player_id == bottom { top } else { bottom }
+}
@@ -0,0 +1,163 @@
+//! Plays a match between two engine configurations, each playing both sides of every opening,
+//! and prints the results with the Elo difference they imply:
+//! `cargo run --release --bin match -- alphabeta:depth=8 mcts:time=200 [--games 100] [--variant american] [--book book.bin] [--plies n] [--concurrency n] [--pdn match.pdn]`
+use std::{
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use engine::tournament::{EngineConfig, Opening, Tally, play_game};
+use rand::{SeedableRng, rngs::SmallRng};
+use store::{
+    opening_book::{DEFAULT_PLIES, OpeningBook},
+    pdn::{PdnGame, today},
+    rules::Variant,
+};
+
+const USAGE: &str = "Usage: match <engine> <engine> [--games 100] [--variant american] [--book book.bin] [--plies n] [--concurrency n] [--pdn match.pdn]\nEngines are alphabeta, mcts or mcts-heuristic, with options like alphabeta:depth=8 or mcts:time=200,playouts=5000 (times in milliseconds)";
+/// random moves opening each pair of games when there is no book
+const RANDOM_PLIES: usize = 4;
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("❌ {err}\n{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let mut configs = vec![];
+    let mut games = 100;
+    let mut variant = Variant::default();
+    let mut book = None;
+    let mut plies = None;
+    let mut concurrency = 1;
+    let mut pdn = PathBuf::from("match.pdn");
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {arg}"));
+        let number = |value: String| {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid number: {value}"))
+        };
+        match arg.as_str() {
+            "--games" => games = number(value()?)?,
+            "--variant" => variant = value()?.parse()?,
+            "--book" => book = Some(PathBuf::from(value()?)),
+            "--plies" => plies = Some(number(value()?)?),
+            "--concurrency" => concurrency = number(value()?)?.max(1),
+            "--pdn" => pdn = PathBuf::from(value()?),
+            _ => configs.push(arg.parse::<EngineConfig>()?),
+        }
+    }
+    let configs: [EngineConfig; 2] = configs
+        .try_into()
+        .map_err(|_| "Expected two engines".to_string())?;
+    let opening = match book {
+        Some(path) => Opening::Book {
+            book: Arc::new(OpeningBook::load(&path)?),
+            plies: plies.unwrap_or(DEFAULT_PLIES),
+        },
+        None => Opening::Random {
+            plies: plies.unwrap_or(RANDOM_PLIES),
+        },
+    };
+    let mut file =
+        File::create(&pdn).map_err(|err| format!("Cannot create {}: {err}", pdn.display()))?;
+
+    println!(
+        "{} vs {}: {games} games of {variant}, {concurrency} at a time",
+        configs[0], configs[1]
+    );
+    let next_pair = AtomicUsize::new(0);
+    let (results_tx, results_rx) = mpsc::channel();
+    let mut tally = Tally::default();
+    thread::scope(|scope| -> Result<(), String> {
+        for _ in 0..concurrency {
+            let results_tx = results_tx.clone();
+            let (configs, opening, next_pair) = (&configs, &opening, &next_pair);
+            scope.spawn(move || {
+                let mut rng = SmallRng::from_entropy();
+                loop {
+                    // each opening is played twice, the engines swapping sides
+                    let pair = next_pair.fetch_add(1, Ordering::Relaxed);
+                    if pair * 2 >= games {
+                        return;
+                    }
+                    let moves = opening.moves(variant, &mut rng);
+                    for first in 0..2 {
+                        let game = pair * 2 + first;
+                        if game >= games {
+                            return;
+                        }
+                        let mut engines = configs.map(|config| config.build());
+                        let result = play_game(configs, &mut engines, first, variant, &moves);
+                        if results_tx.send((game, result)).is_err() {
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        drop(results_tx);
+
+        let date = today();
+        for (game, result) in results_rx {
+            let state = match result {
+                Ok(state) => state,
+                Err(err) => {
+                    println!("❌ Game {}: {err}", game + 1);
+                    continue;
+                }
+            };
+            tally.add(&state);
+
+            let mut record = PdnGame::from_game(&state, &date);
+            record.set_header("Event", "checkeRS engine match")?;
+            record.set_header("Round", &(game + 1).to_string())?;
+            writeln!(file, "{record}")
+                .map_err(|err| format!("Cannot write to {}: {err}", pdn.display()))?;
+
+            println!(
+                "Game {:>3}/{games}: {} - {} {:<7} in {:>3} moves | +{} ={} -{}",
+                game + 1,
+                record.header("White").unwrap_or("?"),
+                record.header("Black").unwrap_or("?"),
+                record.result(),
+                state.moves().count(),
+                tally.wins,
+                tally.draws,
+                tally.losses
+            );
+        }
+        Ok(())
+    })?;
+
+    if tally.games() == 0 {
+        return Err("No game was played to its end".to_string());
+    }
+    println!(
+        "{} vs {}: +{} ={} -{}, scoring {:.1}%",
+        configs[0],
+        configs[1],
+        tally.wins,
+        tally.draws,
+        tally.losses,
+        tally.score() * 100.0
+    );
+    match (tally.elo(), tally.elo_margin()) {
+        (Some(elo), Some(margin)) => println!("Elo difference: {elo:+.1} ± {margin:.1} (95%)"),
+        (Some(elo), None) => println!("Elo difference: {elo:+.1}, too few games for a margin"),
+        (None, _) => println!("Elo difference: unbounded, one engine scored every point"),
+    }
+    println!("games saved to {}", pdn.display());
+    Ok(())
+}
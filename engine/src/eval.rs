@@ -0,0 +1,63 @@
+//! Static evaluation of a position: material first, then how well the pieces are placed.
+use store::{board::Board, coords::Coords, player::PlayerId};
+
+pub const PAWN: i32 = 100;
+pub const KING: i32 = 150;
+/// kings moving any distance are worth a lot more
+pub const FLYING_KING: i32 = 300;
+
+// bonuses, in hundredths of a pawn
+const ADVANCEMENT: i32 = 3;
+const BACK_ROW: i32 = 8;
+const CENTER: i32 = 5;
+
+/// the score of `board` for `player_id`: positive when they are ahead
+pub fn evaluate(board: &Board, player_id: PlayerId) -> i32 {
+    let king = if board.rules().flying_kings() {
+        FLYING_KING
+    } else {
+        KING
+    };
+    let pieces = board.coords().filter(|c| board[*c].is_some()).count();
+    // more than half the pieces of the starting position are still on the board
+    let opening = pieces > board.starting_rows * board.width / 2;
+
+    let mut score = 0;
+    for coords in board.coords() {
+        let Some(piece) = board[coords] else {
+            continue;
+        };
+        // rows between the piece and its own back row
+        let advancement = if board.direction(piece.player_id) == 1 {
+            board.height - 1 - coords.y
+        } else {
+            coords.y
+        } as i32;
+
+        let mut value = if piece.is_king() {
+            king
+        } else {
+            // pawns guarding the back row keep the opponent from crowning, until the endgame
+            let guard = if advancement == 0 && opening {
+                BACK_ROW
+            } else {
+                0
+            };
+            PAWN + ADVANCEMENT * advancement + guard
+        };
+        if is_center(board, coords) {
+            value += CENTER;
+        }
+
+        if piece.player_id == player_id {
+            score += value;
+        } else {
+            score -= value;
+        }
+    }
+    score
+}
+
+fn is_center(board: &Board, coords: Coords) -> bool {
+    (2..board.width - 2).contains(&coords.x) && (2..board.height - 2).contains(&coords.y)
+}
@@ -0,0 +1,163 @@
+//! Computer players: given a game, they pick the move to play.
+pub mod alpha_beta;
+pub mod analysis;
+pub mod eval;
+pub mod mcts;
+pub mod time;
+pub mod tournament;
+
+use std::{
+    fmt,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use store::{
+    game_state::GameState,
+    game_utils::Move,
+    opening_book::{BOOK_FILE, OpeningBook},
+    tablebase::{TABLEBASES_DIR, Tablebases},
+};
+
+use crate::{
+    alpha_beta::AlphaBeta,
+    mcts::{Mcts, Playout},
+};
+
+pub use time::Clock;
+
+/// how long an engine can think about a move: it stops at whichever limit comes first
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    /// moves to look ahead, captures excluded; engines playing games out have no depth and
+    /// ignore it, searching with their other limits, or their default playouts without any
+    pub depth: Option<usize>,
+    pub time: Option<Duration>,
+    /// the time left to the player for the rest of the game, shared between the moves to come
+    pub clock: Option<Clock>,
+    /// games simulated, for the engines that play them out rather than searching
+    pub playouts: Option<u64>,
+}
+impl Limits {
+    pub fn depth(depth: usize) -> Self {
+        Limits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+    pub fn time(time: Duration) -> Self {
+        Limits {
+            time: Some(time),
+            ..Default::default()
+        }
+    }
+    pub fn clock(remaining: Duration, increment: Duration) -> Self {
+        Limits {
+            clock: Some(Clock {
+                remaining,
+                increment,
+            }),
+            ..Default::default()
+        }
+    }
+    pub fn playouts(playouts: u64) -> Self {
+        Limits {
+            playouts: Some(playouts),
+            ..Default::default()
+        }
+    }
+    /// whether the search can stop before its depth limit
+    pub fn is_timed(&self) -> bool {
+        self.time.is_some() || self.clock.is_some()
+    }
+}
+
+pub trait Engine {
+    /// the move the player whose turn it is should play, if they can move at all
+    fn best_move(&mut self, state: &GameState, limits: Limits) -> Option<Move>;
+}
+
+/// the engines to choose from, e.g. on the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum EngineKind {
+    #[default]
+    AlphaBeta,
+    /// with the way it plays games out
+    Mcts(Playout),
+}
+impl EngineKind {
+    pub const ALL: [EngineKind; 3] = [
+        EngineKind::AlphaBeta,
+        EngineKind::Mcts(Playout::Random),
+        EngineKind::Mcts(Playout::Heuristic),
+    ];
+
+    pub fn build(self) -> Box<dyn Engine + Send> {
+        self.build_with(None, None)
+    }
+    /// like `build`, the engines that can use them playing their first moves from `book` and
+    /// the endgames in `tablebases` perfectly
+    pub fn build_with(
+        self,
+        book: Option<Arc<OpeningBook>>,
+        tablebases: Option<Arc<Tablebases>>,
+    ) -> Box<dyn Engine + Send> {
+        match self {
+            EngineKind::AlphaBeta => {
+                let mut engine = AlphaBeta::new();
+                if let Some(book) = book {
+                    engine = engine.with_book(book);
+                }
+                if let Some(tablebases) = tablebases {
+                    engine = engine.with_tablebases(tablebases);
+                }
+                Box::new(engine)
+            }
+            EngineKind::Mcts(playout) => Box::new(Mcts::new().with_playout(playout)),
+        }
+    }
+}
+
+impl fmt::Display for EngineKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EngineKind::AlphaBeta => "alphabeta",
+            EngineKind::Mcts(Playout::Random) => "mcts",
+            EngineKind::Mcts(Playout::Heuristic) => "mcts-heuristic",
+        };
+        write!(f, "{name}")
+    }
+}
+impl FromStr for EngineKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EngineKind::ALL
+            .into_iter()
+            .find(|kind| kind.to_string() == s.to_lowercase())
+            .ok_or(format!("Unknown engine: {s}"))
+    }
+}
+
+/// the book built with `cargo run --bin opening-book` into `BOOK_FILE`, if there is one: it is
+/// read once, and shared by the engines playing in the client and the bot
+pub fn default_book() -> Option<Arc<OpeningBook>> {
+    static BOOK: OnceLock<Option<Arc<OpeningBook>>> = OnceLock::new();
+    BOOK.get_or_init(|| OpeningBook::load(Path::new(BOOK_FILE)).ok().map(Arc::new))
+        .clone()
+}
+
+/// the tables generated with `cargo run --bin tablebase` into `TABLEBASES_DIR`, if there are
+/// any: they are read once, and shared by every engine built afterwards
+pub fn default_tablebases() -> Option<Arc<Tablebases>> {
+    static TABLEBASES: OnceLock<Option<Arc<Tablebases>>> = OnceLock::new();
+    TABLEBASES
+        .get_or_init(|| {
+            Tablebases::load(Path::new(TABLEBASES_DIR))
+                .ok()
+                .map(Arc::new)
+        })
+        .clone()
+}
@@ -0,0 +1,313 @@
+//! Monte Carlo tree search: instead of evaluating positions, plays many games out from them
+//! and picks the move that led to the most wins. The tree grows towards the moves doing well,
+//! each step down balancing the moves with the best results against the ones tried the least
+//! (UCT), so it needs no hand-written evaluation.
+use rand::{Rng, SeedableRng, rngs::SmallRng, seq::SliceRandom};
+use store::{
+    board::Board,
+    game_state::GameState,
+    game_utils::{Move, legal_moves},
+    player::PlayerId,
+};
+
+use crate::{Engine, Limits, eval::evaluate, time::TimeManager};
+
+/// how many games to play out when no limit is given
+pub const DEFAULT_PLAYOUTS: u64 = 20_000;
+/// how much UCT favors the moves tried the least over the ones doing best
+pub const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+/// plies after which a game played out is counted as a draw
+const MAX_PLAYOUT_PLIES: usize = 150;
+/// noise added to the evaluation of heuristic playouts, so that they don't all play the same
+const HEURISTIC_NOISE: i32 = 20;
+
+/// how the games are played out from the positions added to the tree
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Playout {
+    /// random moves, knowing nothing about the game
+    #[default]
+    Random,
+    /// the move with the best evaluation, give or take some noise
+    Heuristic,
+}
+
+#[derive(Debug)]
+struct Node {
+    /// the move leading here from the parent, `None` at the root
+    mv: Option<Move>,
+    /// the player who played that move
+    mover: PlayerId,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// the moves not in the tree yet
+    untried: Vec<Move>,
+    visits: u32,
+    /// games won by the mover from here, draws counting as half
+    wins: f64,
+}
+
+#[derive(Debug)]
+pub struct Mcts {
+    /// games played out by the last search
+    pub playouts: u64,
+    pub playout: Playout,
+    rng: SmallRng,
+}
+impl Default for Mcts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl Mcts {
+    pub fn new() -> Self {
+        Self::with_rng(SmallRng::from_entropy())
+    }
+    /// an engine playing the same moves every time, given the same positions and limits
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(SmallRng::seed_from_u64(seed))
+    }
+    fn with_rng(rng: SmallRng) -> Self {
+        Mcts {
+            playouts: 0,
+            playout: Playout::default(),
+            rng,
+        }
+    }
+    pub fn with_playout(mut self, playout: Playout) -> Self {
+        self.playout = playout;
+        self
+    }
+
+    /// one round: down the tree to a leaf, a new node for one of its moves, a game played out
+    /// from there, and its result counted in every node on the way
+    fn iterate(&mut self, tree: &mut Vec<Node>, root: &Board, root_player: PlayerId) {
+        let mut board = root.clone();
+        let mut turn = root_player;
+        let mut node = 0;
+
+        while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+            node = select(tree, node);
+            board.apply(tree[node].mv.as_ref().unwrap()).unwrap();
+            turn = opponent(&board, turn);
+        }
+
+        if !tree[node].untried.is_empty() {
+            let index = self.rng.gen_range(0..tree[node].untried.len());
+            let mv = tree[node].untried.swap_remove(index);
+            board.apply(&mv).unwrap();
+            let mover = turn;
+            turn = opponent(&board, turn);
+            tree.push(Node {
+                mv: Some(mv),
+                mover,
+                parent: Some(node),
+                children: vec![],
+                untried: legal_moves(&board, turn),
+                visits: 0,
+                wins: 0.0,
+            });
+            let child = tree.len() - 1;
+            tree[node].children.push(child);
+            node = child;
+        }
+
+        let winner = self.play_out(&mut board, turn);
+        let mut current = Some(node);
+        while let Some(node) = current {
+            let node = &mut tree[node];
+            node.visits += 1;
+            node.wins += match winner {
+                Some(winner) if winner == node.mover => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            current = node.parent;
+        }
+        self.playouts += 1;
+    }
+
+    /// plays the game to its end, `None` if it goes on too long to tell
+    fn play_out(&mut self, board: &mut Board, mut turn: PlayerId) -> Option<PlayerId> {
+        for _ in 0..MAX_PLAYOUT_PLIES {
+            let moves = legal_moves(board, turn);
+            let mv = match self.playout {
+                Playout::Random => moves.choose(&mut self.rng),
+                Playout::Heuristic => moves.iter().max_by_key(|mv| {
+                    let mut child = board.clone();
+                    child.apply(mv).unwrap();
+                    evaluate(&child, turn) + self.rng.gen_range(0..=HEURISTIC_NOISE)
+                }),
+            };
+            let Some(mv) = mv else {
+                return Some(opponent(board, turn));
+            };
+            board.apply(mv).unwrap();
+            turn = opponent(board, turn);
+        }
+        None
+    }
+}
+
+impl Engine for Mcts {
+    /// plays games out until a limit is reached, and plays the move tried the most; there is no
+    /// depth to limit, so `limits.depth` is ignored and without playouts or time
+    /// `DEFAULT_PLAYOUTS` games are played
+    fn best_move(&mut self, state: &GameState, limits: Limits) -> Option<Move> {
+        let board = &state.grid;
+        let player_id = state.is_turn;
+        let mut moves = legal_moves(board, player_id);
+        if moves.len() <= 1 {
+            return moves.pop();
+        }
+
+        let time = TimeManager::new(&limits, state.moves().count() / 2);
+        let max_playouts = match limits.playouts {
+            None if limits.is_timed() => u64::MAX,
+            playouts => playouts.unwrap_or(DEFAULT_PLAYOUTS),
+        };
+        self.playouts = 0;
+        let mut tree = vec![Node {
+            mv: None,
+            mover: opponent(board, player_id),
+            parent: None,
+            children: vec![],
+            untried: moves,
+            visits: 0,
+            wins: 0.0,
+        }];
+        while self.playouts < max_playouts && !time.is_some_and(|time| time.is_over()) {
+            self.iterate(&mut tree, board, player_id);
+        }
+
+        tree[0]
+            .children
+            .iter()
+            .max_by_key(|child| tree[**child].visits)
+            .and_then(|child| tree[*child].mv.clone())
+    }
+}
+
+/// the child with the best upper confidence bound: its win rate, plus a bonus shrinking as it
+/// gets tried
+fn select(tree: &[Node], node: usize) -> usize {
+    let log_visits = (tree[node].visits as f64).ln();
+    let uct = |child: usize| {
+        let child = &tree[child];
+        let visits = child.visits as f64;
+        child.wins / visits + EXPLORATION * (log_visits / visits).sqrt()
+    };
+    *tree[node]
+        .children
+        .iter()
+        .max_by(|a, b| uct(**a).total_cmp(&uct(**b)))
+        .unwrap()
+}
+
+fn opponent(board: &Board, player_id: PlayerId) -> PlayerId {
+    let [bottom, top] = board.players();
+    if player_id == bottom { top } else { bottom }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use store::{pdn::notation, player::Player, rules::Variant};
+
+    use super::*;
+
+    const WHITE: PlayerId = 1;
+    const BLACK: PlayerId = 2;
+
+    fn game(fen: &str) -> GameState {
+        let (board, turn) = Board::from_fen(fen, Variant::American, [WHITE, BLACK]).unwrap();
+        let players = [(WHITE, 1), (BLACK, -1)]
+            .map(|(id, direction)| {
+                let name = id.to_string();
+                let player = Player {
+                    id,
+                    name,
+                    direction,
+                    score: 0,
+                };
+                (id, player)
+            })
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        GameState::from_position(players, board, turn)
+    }
+    fn node(parent: Option<usize>, visits: u32, wins: f64) -> Node {
+        Node {
+            mv: None,
+            mover: WHITE,
+            parent,
+            children: vec![],
+            untried: vec![],
+            visits,
+            wins,
+        }
+    }
+    fn best_move(fen: &str, playout: Playout) -> String {
+        let state = game(fen);
+        let mv = Mcts::with_seed(7)
+            .with_playout(playout)
+            .best_move(&state, Limits::playouts(500))
+            .unwrap();
+        notation(&state.grid, &mv)
+    }
+
+    #[test]
+    fn selection_balances_results_and_tries() {
+        let mut tree = vec![
+            node(None, 10, 5.0),
+            node(Some(0), 5, 4.0),
+            node(Some(0), 5, 1.0),
+        ];
+        tree[0].children = vec![1, 2];
+        // as often tried, the move doing best
+        assert_eq!(select(&tree, 0), 1);
+        // but a move hardly tried gets its chance
+        tree[1] = node(Some(0), 9, 5.0);
+        tree[2] = node(Some(0), 1, 0.0);
+        assert_eq!(select(&tree, 0), 2);
+    }
+
+    #[test]
+    fn finds_a_win_in_one() {
+        // the black pawn has nowhere to go once the king stands in front of it
+        for playout in [Playout::Random, Playout::Heuristic] {
+            assert_eq!(best_move("W:WK27:B28", playout), "27-32", "{playout:?}");
+        }
+    }
+
+    #[test]
+    fn prefers_the_capture_taking_more_pieces() {
+        for playout in [Playout::Random, Playout::Heuristic] {
+            assert_eq!(
+                best_move("W:W30,21:B26,19,17,1", playout),
+                "30x23x16",
+                "{playout:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn plays_the_only_move_without_searching() {
+        let mut mcts = Mcts::with_seed(7);
+        let mv = mcts.best_move(&game("W:W23:B18"), Limits::default());
+        assert!(mv.is_some());
+        assert_eq!(mcts.playouts, 0);
+        assert_eq!(
+            mcts.best_move(&game("W:W29:B25,22"), Limits::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn playouts_limit_the_search() {
+        let mut mcts = Mcts::with_seed(7);
+        let state = game("W:WK27:B28");
+        mcts.best_move(&state, Limits::playouts(300));
+        assert_eq!(mcts.playouts, 300);
+    }
+}
@@ -0,0 +1,150 @@
+//! Time management: how long to think about a move, given the limits of the search and, when
+//! playing under a clock, the time left for the rest of the game.
+use std::time::{Duration, Instant};
+
+use crate::Limits;
+
+/// moves a player is expected to make in a game
+const MOVES_PER_GAME: u32 = 50;
+/// the fewest moves the remaining time is split across, even late in long games
+const MIN_MOVES_TO_GO: u32 = 15;
+
+/// a player's clock
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Clock {
+    pub remaining: Duration,
+    /// added to the clock after each move
+    pub increment: Duration,
+}
+
+impl Clock {
+    /// the time to spend on the next move: the remaining time split across the expected moves
+    /// to go, plus most of the increment
+    pub fn allocate(&self, moves_played: usize) -> Duration {
+        let moves_to_go = MOVES_PER_GAME
+            .saturating_sub(moves_played as u32)
+            .max(MIN_MOVES_TO_GO);
+        (self.remaining / moves_to_go + self.increment * 3 / 4).min(self.remaining / 2)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TimeManager {
+    start: Instant,
+    /// the time the search aims at
+    target: Duration,
+    /// the time the search must stop at, even in the middle of an iteration
+    limit: Duration,
+}
+
+impl TimeManager {
+    /// `None` if the search has no time limit; `moves_played` counts the player's own moves
+    pub fn new(limits: &Limits, moves_played: usize) -> Option<Self> {
+        let from_clock = limits.clock.map(|clock| {
+            let target = clock.allocate(moves_played);
+            // an iteration running late may overrun the target, but never eat up the clock
+            (target, (target * 3).min(clock.remaining / 2))
+        });
+        let from_time = limits.time.map(|time| (time, time));
+        let (target, limit) = match (from_clock, from_time) {
+            (Some(clock), Some(time)) => (clock.0.min(time.0), clock.1.min(time.1)),
+            (clock, time) => clock.or(time)?,
+        };
+        Some(TimeManager {
+            start: Instant::now(),
+            target,
+            limit,
+        })
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.start + self.limit
+    }
+
+    /// whether the time aimed at is used up, for searches that can stop at any moment
+    pub fn is_over(&self) -> bool {
+        self.start.elapsed() >= self.target
+    }
+
+    /// whether the next iteration is likely to finish in time: each one takes a few times longer
+    /// than the one before, so there is no point in starting past half the target
+    pub fn can_start_iteration(&self) -> bool {
+        self.start.elapsed() < self.target / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(remaining: u64, increment: u64) -> Clock {
+        Clock {
+            remaining: Duration::from_secs(remaining),
+            increment: Duration::from_secs(increment),
+        }
+    }
+
+    #[test]
+    fn the_clock_is_shared_between_the_moves_to_go() {
+        assert_eq!(clock(50, 0).allocate(0), Duration::from_secs(1));
+        assert_eq!(clock(50, 0).allocate(25), Duration::from_secs(2));
+        // late in the game, the time left still goes to a few more moves
+        assert_eq!(clock(30, 0).allocate(49), Duration::from_secs(2));
+        assert_eq!(clock(30, 0).allocate(200), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn most_of_the_increment_is_spent() {
+        assert_eq!(clock(50, 4).allocate(0), Duration::from_secs(4));
+        assert_eq!(clock(0, 4).allocate(0), Duration::ZERO);
+    }
+
+    #[test]
+    fn a_move_never_takes_more_than_half_the_clock() {
+        assert_eq!(clock(2, 10).allocate(0), Duration::from_secs(1));
+        assert_eq!(clock(10, 60).allocate(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn the_search_stops_at_the_first_limit() {
+        assert!(TimeManager::new(&Limits::default(), 0).is_none());
+        assert!(TimeManager::new(&Limits::depth(6), 0).is_none());
+
+        let time = TimeManager::new(&Limits::time(Duration::from_secs(3)), 0).unwrap();
+        assert_eq!(
+            (time.target, time.limit),
+            (Duration::from_secs(3), Duration::from_secs(3))
+        );
+
+        // a running iteration may go on past the target, up to three times as long
+        let limits = Limits::clock(Duration::from_secs(100), Duration::ZERO);
+        let time = TimeManager::new(&limits, 0).unwrap();
+        assert_eq!(
+            (time.target, time.limit),
+            (Duration::from_secs(2), Duration::from_secs(6))
+        );
+
+        let limits = Limits {
+            time: Some(Duration::from_secs(1)),
+            ..limits
+        };
+        let time = TimeManager::new(&limits, 0).unwrap();
+        assert_eq!(
+            (time.target, time.limit),
+            (Duration::from_secs(1), Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn time_runs_out_from_the_start_of_the_search() {
+        let time = TimeManager::new(&Limits::time(Duration::ZERO), 0).unwrap();
+        assert!(time.is_over());
+        assert!(!time.can_start_iteration());
+        assert!(time.deadline() <= Instant::now());
+
+        let time = TimeManager::new(&Limits::time(Duration::from_secs(3600)), 0).unwrap();
+        assert!(!time.is_over());
+        assert!(time.can_start_iteration());
+        assert!(time.deadline() > Instant::now() + Duration::from_secs(3000));
+    }
+}
@@ -0,0 +1,293 @@
+//! Matches between two engine configurations, to tell whether a change makes an engine
+//! stronger: games are played in pairs from the same opening, each engine playing both sides,
+//! and the results are tallied into an Elo difference with its margin of error.
+use std::{collections::HashMap, fmt, str::FromStr, sync::Arc, time::Duration};
+
+use rand::{Rng, seq::SliceRandom};
+use store::{
+    game_state::{EndGameReason, GameEvent, GameState},
+    game_utils::{Move, legal_moves},
+    opening_book::OpeningBook,
+    player::{Player, PlayerId},
+    rules::Variant,
+};
+
+use crate::{Engine, EngineKind, Limits, default_tablebases};
+
+/// the ids of the players of the two engines, whichever side they play
+pub const PLAYER_IDS: [PlayerId; 2] = [1, 2];
+/// attempts at finding an opening that doesn't end the game
+const OPENING_ATTEMPTS: usize = 100;
+
+/// an engine with the limits it searches within, e.g. `alphabeta:depth=6` or
+/// `mcts-heuristic:time=100,playouts=5000`, times being in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EngineConfig {
+    pub kind: EngineKind,
+    pub limits: Limits,
+}
+impl EngineConfig {
+    /// the engine with the tablebases, but without an opening book: the openings of a match
+    /// are played by `Opening`
+    pub fn build(&self) -> Box<dyn Engine + Send> {
+        self.kind.build_with(None, default_tablebases())
+    }
+}
+impl fmt::Display for EngineConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options = vec![];
+        if let Some(depth) = self.limits.depth {
+            options.push(format!("depth={depth}"));
+        }
+        if let Some(time) = self.limits.time {
+            options.push(format!("time={}", time.as_millis()));
+        }
+        if let Some(playouts) = self.limits.playouts {
+            options.push(format!("playouts={playouts}"));
+        }
+        if options.is_empty() {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "{}:{}", self.kind, options.join(","))
+        }
+    }
+}
+impl FromStr for EngineConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, options) = s.split_once(':').unwrap_or((s, ""));
+        let mut config = EngineConfig {
+            kind: kind.parse()?,
+            limits: Limits::default(),
+        };
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (name, value) = option
+                .split_once('=')
+                .ok_or(format!("Expected an option like depth=6, found {option}"))?;
+            let invalid = |_| format!("Invalid value for {name}: {value}");
+            match name {
+                "depth" if matches!(config.kind, EngineKind::Mcts(_)) => {
+                    return Err(format!(
+                        "{} plays games out rather than searching to a depth, limit its playouts or time instead",
+                        config.kind
+                    ));
+                }
+                "depth" => config.limits.depth = Some(value.parse().map_err(invalid)?),
+                "time" => {
+                    config.limits.time =
+                        Some(Duration::from_millis(value.parse().map_err(invalid)?))
+                }
+                "playouts" => config.limits.playouts = Some(value.parse().map_err(invalid)?),
+                _ => return Err(format!("Unknown engine option: {name}")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// how the first moves of each pair of games are chosen, so that they don't all play the same
+#[derive(Debug, Clone)]
+pub enum Opening {
+    /// moves at random, none to play from the starting position
+    Random { plies: usize },
+    /// book moves, picked as often as they were played, until out of the book
+    Book {
+        book: Arc<OpeningBook>,
+        plies: usize,
+    },
+}
+impl Opening {
+    /// the first moves of a game of `variant`, the player moving first sitting at the bottom,
+    /// as in the games of `play_game`
+    pub fn moves(&self, variant: Variant, rng: &mut impl Rng) -> Vec<Move> {
+        for _ in 0..OPENING_ATTEMPTS {
+            let mut state = new_game(variant, 0, ["", ""]);
+            let mut moves = vec![];
+            let plies = match self {
+                Opening::Random { plies } | Opening::Book { plies, .. } => *plies,
+            };
+            for _ in 0..plies {
+                let mv = match self {
+                    Opening::Random { .. } => {
+                        legal_moves(&state.grid, state.is_turn).choose(rng).cloned()
+                    }
+                    Opening::Book { book, .. } => book.pick(&state.grid, state.is_turn),
+                };
+                let Some(mv) = mv else {
+                    break;
+                };
+                if play(&mut state, &mv).unwrap() {
+                    break;
+                }
+                moves.push(mv);
+            }
+            if moves.len() == plies || matches!(self, Opening::Book { .. }) {
+                return moves;
+            }
+        }
+        vec![]
+    }
+}
+
+/// plays a game between the engines of `configs`, the one of index `first` moving first,
+/// starting with the moves of `opening`; the game returned has ended
+pub fn play_game(
+    configs: &[EngineConfig; 2],
+    engines: &mut [Box<dyn Engine + Send>; 2],
+    first: usize,
+    variant: Variant,
+    opening: &[Move],
+) -> Result<GameState, String> {
+    let names = configs.map(|config| config.to_string());
+    let mut state = new_game(variant, first, [names[0].as_str(), names[1].as_str()]);
+    for mv in opening {
+        if play(&mut state, mv)? {
+            return Err("The opening ends the game".to_string());
+        }
+    }
+    loop {
+        let engine = PLAYER_IDS
+            .iter()
+            .position(|id| *id == state.is_turn)
+            .unwrap();
+        let mv = engines[engine]
+            .best_move(&state, configs[engine].limits)
+            .ok_or(format!("{} found no move to play", names[engine]))?;
+        if play(&mut state, &mv)? {
+            return Ok(state);
+        }
+    }
+}
+
+/// a new game between the players of `PLAYER_IDS`, the one of index `first` moving first
+/// from the bottom
+fn new_game(variant: Variant, first: usize, names: [&str; 2]) -> GameState {
+    let players = PLAYER_IDS
+        .iter()
+        .zip(names)
+        .enumerate()
+        .map(|(index, (id, name))| {
+            let player = Player {
+                id: *id,
+                name: name.to_string(),
+                direction: if index == first { 1 } else { -1 },
+                score: 0,
+            };
+            (*id, player)
+        })
+        .collect::<HashMap<_, _>>();
+    GameState::new(players, PLAYER_IDS[first], variant)
+}
+
+/// plays a move as the server would, ending the game or passing the turn; whether it ended
+fn play(state: &mut GameState, mv: &Move) -> Result<bool, String> {
+    state.dispatch(&GameEvent::Move {
+        mv: mv.clone(),
+        player_id: state.is_turn,
+    })?;
+    if let Some(end_game) = state.check_end_game() {
+        state.dispatch(&end_game)?;
+        return Ok(true);
+    }
+    let player_id = *state
+        .players
+        .keys()
+        .find(|id| **id != state.is_turn)
+        .unwrap();
+    state.dispatch(&GameEvent::TurnChanged { player_id })?;
+    Ok(false)
+}
+
+/// the results of a match for the first engine
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tally {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+impl Tally {
+    /// counts a game of `play_game`, unless it didn't end
+    pub fn add(&mut self, state: &GameState) {
+        let reason = state.history().iter().rev().find_map(|event| match event {
+            GameEvent::EndGame { reason } => Some(reason),
+            _ => None,
+        });
+        match reason {
+            Some(EndGameReason::PlayerWon { winner }) if *winner == PLAYER_IDS[0] => self.wins += 1,
+            Some(EndGameReason::PlayerLeft { player_id }) if *player_id != PLAYER_IDS[0] => {
+                self.wins += 1
+            }
+            Some(EndGameReason::PlayerWon { .. } | EndGameReason::PlayerLeft { .. }) => {
+                self.losses += 1
+            }
+            Some(EndGameReason::Draw { .. }) => self.draws += 1,
+            None => {}
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+    /// the points scored per game, a draw counting as half a win
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    /// the Elo difference the score implies, `None` when it has no bounds as one engine won
+    /// every game
+    pub fn elo(&self) -> Option<f64> {
+        elo(self.score())
+    }
+    /// how far the real Elo difference can be from `elo`, at 95% confidence
+    pub fn elo_margin(&self) -> Option<f64> {
+        let games = self.games() as f64;
+        let score = self.score();
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / games;
+        // 1.96 standard errors of the mean score either side
+        let deviation = 1.96 * (variance / games).sqrt();
+        Some((elo(score + deviation)? - elo(score - deviation)?) / 2.0)
+    }
+}
+
+/// the Elo difference that makes the stronger player expected to score `score`
+fn elo(score: f64) -> Option<f64> {
+    (score > 0.0 && score < 1.0).then(|| 400.0 * (score / (1.0 - score)).log10())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcts::Playout;
+
+    #[test]
+    fn configs_round_trip_through_their_name() {
+        for name in [
+            "alphabeta",
+            "alphabeta:depth=6,time=100",
+            "mcts:playouts=5000",
+            "mcts-heuristic:time=200,playouts=100",
+        ] {
+            assert_eq!(name.parse::<EngineConfig>().unwrap().to_string(), name);
+        }
+        let config = "MCTS-Heuristic".parse::<EngineConfig>().unwrap();
+        assert_eq!(config.kind, EngineKind::Mcts(Playout::Heuristic));
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        for name in [
+            "minimax",
+            "alphabeta:depth",
+            "alphabeta:depth=deep",
+            "alphabeta:speed=1",
+            "mcts:depth=6",
+            "mcts-heuristic:depth=6",
+        ] {
+            assert!(name.parse::<EngineConfig>().is_err(), "{name}");
+        }
+    }
+}
@@ -2,19 +2,202 @@ use cli_log::info;
 use renet::{ConnectionConfig, DefaultChannel, RenetServer, ServerEvent};
 use renet_netcode::{NetcodeServerTransport, ServerAuthentication, ServerConfig};
 use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant, SystemTime};
 use store::PROTOCOL_ID;
-use store::game_state::{EndGameReason, GameEvent, GameState};
+use store::ballot::{BALLOT_FILE, Ballot};
+use store::event_log::EventLog;
+use store::game_state::{DrawKind, EndGameReason, GameEvent, GameState};
+use store::pdn::{PdnGame, notation, today};
 use store::player::{Player, PlayerId};
+use store::rules::Variant;
+use store::tablebase::{Outcome, TABLEBASES_DIR, Tablebases};
 use store::utils::from_user_data;
 
+// finished games are appended here, one PDN record after the other
+const GAMES_ARCHIVE: &str = "games.pdn";
+// the event log of each finished game is saved here, to replay it with `checkers-replay`
+const LOGS_DIR: &str = "logs";
+// the game in progress when the server shuts down, resumed when it starts again
+const SAVE_FILE: &str = "game.save";
+
+fn save_game(state: &GameState) {
+    let written = postcard::to_allocvec(state)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| fs::write(SAVE_FILE, bytes).map_err(|err| err.to_string()));
+    match written {
+        Ok(_) => info!("💾 Game in progress saved to {SAVE_FILE}"),
+        Err(err) => info!("❌ Cannot save the game in progress to {SAVE_FILE}: {err}"),
+    }
+}
+fn load_game() -> Option<GameState> {
+    let bytes = fs::read(SAVE_FILE).ok()?;
+    match postcard::from_bytes::<GameState>(&bytes) {
+        Ok(state) => Some(state),
+        Err(err) => {
+            info!("❌ Cannot load the saved game from {SAVE_FILE}: {err}");
+            None
+        }
+    }
+}
+
+fn archive_game(state: &GameState) {
+    let record = PdnGame::from_game(state, &today());
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(GAMES_ARCHIVE)
+        .and_then(|mut file| writeln!(file, "{record}"));
+    match written {
+        Ok(_) => info!("📜 Game saved to {GAMES_ARCHIVE}"),
+        Err(err) => info!("❌ Cannot save the game to {GAMES_ARCHIVE}: {err}"),
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let path = format!("{LOGS_DIR}/game-{timestamp}.log");
+    let written = EventLog::from_game(state).to_bytes().and_then(|bytes| {
+        fs::create_dir_all(LOGS_DIR)
+            .and_then(|_| fs::write(&path, bytes))
+            .map_err(|err| err.to_string())
+    });
+    match written {
+        Ok(_) => info!("📜 Event log saved to {path}"),
+        Err(err) => info!("❌ Cannot save the event log to {path}: {err}"),
+    }
+}
+
+/// ends the game in a draw once the tablebases show that neither player can win any more
+fn adjudicate(state: &GameState, tablebases: &Tablebases) -> Option<GameEvent> {
+    match tablebases.probe_board(&state.grid, state.is_turn)? {
+        Outcome::Draw => Some(GameEvent::EndGame {
+            reason: EndGameReason::Draw {
+                kind: DrawKind::Adjudication,
+            },
+        }),
+        Outcome::Win(_) | Outcome::Loss(_) => None,
+    }
+}
+
+/// the openings of the three-move ballot: the list in `BALLOT_FILE`, or the standard one
+fn load_ballot() -> Ballot {
+    match Ballot::load(Path::new(BALLOT_FILE)) {
+        Ok(ballot) => {
+            info!(
+                "🎲 Drawing openings from the {} in {BALLOT_FILE}",
+                ballot.len()
+            );
+            ballot
+        }
+        Err(err) => {
+            let ballot = Ballot::standard();
+            info!(
+                "🎲 {err}, drawing openings from the {} standard ones instead",
+                ballot.len()
+            );
+            ballot
+        }
+    }
+}
+
+/// plays the first moves of the game, drawn from the ballot, as if the players had
+fn play_ballot(server: &mut RenetServer, state: &mut GameState, ballot: &Ballot) {
+    let opening = ballot.draw(&state.grid, state.is_turn);
+    let moves = opening.iter().map(|mv| notation(&state.grid, mv));
+    info!("🎲 Ballot opening: {}", moves.collect::<Vec<_>>().join(" "));
+    for mv in opening {
+        let player_id = state.is_turn;
+        let Some(next_player) = state.players.keys().copied().find(|id| *id != player_id) else {
+            return;
+        };
+        let events = [
+            GameEvent::Move { mv, player_id },
+            GameEvent::TurnChanged {
+                player_id: next_player,
+            },
+        ];
+        for event in events {
+            if let Err(err) = state.dispatch(&event) {
+                info!("❌ Cannot play the ballot opening: {err}");
+                return;
+            }
+            server.broadcast_message(
+                DefaultChannel::ReliableOrdered,
+                postcard::to_allocvec(&event).unwrap(),
+            );
+        }
+    }
+}
+
 fn main() {
     env_logger::Builder::from_default_env()
         .filter_level(cli_log::LevelFilter::Info)
         .init();
+
+    // the variant every game on this server is played with, e.g. `cargo run --bin server -- pool`,
+    // and with `--ballot` american games open with three moves drawn from the ballot
+    let mut variant = Variant::default();
+    let mut use_ballot = false;
+    for arg in std::env::args().skip(1) {
+        if arg == "--ballot" {
+            use_ballot = true;
+            continue;
+        }
+        match arg.parse() {
+            Ok(parsed) => variant = parsed,
+            Err(err) => {
+                info!("❌ {err}, available variants: {:?}", Variant::ALL);
+                return;
+            }
+        }
+    }
+    let ballot = match (use_ballot, variant) {
+        (false, _) => None,
+        (true, Variant::American) => Some(load_ballot()),
+        (true, _) => {
+            info!("❌ The three-move ballot is for american checkers only");
+            return;
+        }
+    };
+
+    // generated with `cargo run --release --bin tablebase -- generate <pieces>`
+    let tablebases = match Tablebases::load(Path::new(TABLEBASES_DIR)) {
+        Ok(tablebases) => {
+            info!(
+                "📚 Adjudicating with the tablebases up to {} pieces",
+                tablebases.max_pieces()
+            );
+            Some(tablebases)
+        }
+        Err(err) => {
+            info!("📚 No adjudication: {err}");
+            None
+        }
+    };
+
     let mut server = RenetServer::new(ConnectionConfig::default());
-    let mut game_state: Option<GameState> = None;
+    let mut game_state: Option<GameState> = load_game();
+    // a saved game waits for its players to reconnect before going on
+    let mut resuming = game_state.is_some();
+    if let Some(state) = &game_state {
+        let names = state.players.values().map(|p| p.name.clone());
+        info!(
+            "💾 Resuming a {} game, waiting for {}",
+            state.grid.variant(),
+            names.collect::<Vec<_>>().join(" and ")
+        );
+    }
+
+    // on Ctrl-C, the game in progress is saved before shutting down
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    ctrlc::set_handler(move || handler_running.store(false, Ordering::SeqCst)).unwrap();
 
     // Setup transport layer using renet_netcode
     const SERVER_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 5000);
@@ -32,9 +215,20 @@ fn main() {
     let mut last_updated = Instant::now();
     let mut starting_player_id: Option<PlayerId> = None;
     let mut players: HashMap<PlayerId, Player> = HashMap::new();
-    info!("🕹 server listening on {}", SERVER_ADDR);
+    let mut game_over = false;
+    info!("🕹 server listening on {}, playing {}", SERVER_ADDR, variant);
 
     loop {
+        if !running.load(Ordering::SeqCst) {
+            info!("👋 Shutting down...");
+            if let Some(state) = &game_state {
+                save_game(state);
+            }
+            server.disconnect_all();
+            transport.send_packets(&mut server);
+            break;
+        }
+
         let now = Instant::now();
         let duration = now - last_updated;
         last_updated = now;
@@ -51,7 +245,61 @@ fn main() {
                         "🥳 Client connected! {} with username {}",
                         client_id, username
                     );
-                    if server.connected_clients() < 2 {
+
+                    // players of a saved game get their seat back, whatever their new id
+                    if resuming && let Some(state) = &mut game_state {
+                        let seat = state
+                            .players
+                            .values()
+                            .find(|p| p.name == username && !players.contains_key(&p.id))
+                            .map(|p| p.id);
+                        let Some(seat) = seat else {
+                            info!("🚫 {username} is not playing the saved game");
+                            server.disconnect(client_id);
+                            continue;
+                        };
+                        state.reseat(seat, client_id);
+
+                        for player in players.values() {
+                            let event = GameEvent::PlayerJoined {
+                                player: player.clone(),
+                            };
+                            server.send_message(
+                                client_id,
+                                DefaultChannel::ReliableOrdered,
+                                postcard::to_allocvec(&event).unwrap(),
+                            );
+                        }
+                        let player = state.players[&client_id].clone();
+                        let joined_event = GameEvent::PlayerJoined {
+                            player: player.clone(),
+                        };
+                        server.broadcast_message(
+                            DefaultChannel::ReliableOrdered,
+                            postcard::to_allocvec(&joined_event).unwrap(),
+                        );
+                        players.insert(client_id, player);
+
+                        if players.len() == 2 {
+                            info!("✨ resuming the saved game...");
+                            let resume_game = GameEvent::GameResumed {
+                                log: Box::new(EventLog::from_game(state)),
+                            };
+                            server.broadcast_message(
+                                DefaultChannel::ReliableOrdered,
+                                postcard::to_allocvec(&resume_game).unwrap(),
+                            );
+                            resuming = false;
+                            if let Err(err) = fs::remove_file(SAVE_FILE) {
+                                info!("❌ Cannot remove {SAVE_FILE}: {err}");
+                            }
+                        }
+                        continue;
+                    }
+
+                    // counted from the players seated rather than the clients connected, which
+                    // already include the next one when both connect at once
+                    if players.is_empty() {
                         starting_player_id = Some(client_id);
                     }
 
@@ -88,8 +336,13 @@ fn main() {
 
                     players.insert(client_id, new_player);
 
-                    if server.connected_clients() == 2 {
+                    if players.len() == 2 {
                         info!("✨ starting the game...");
+                        let announce_variant = GameEvent::VariantAnnounced { variant };
+                        server.broadcast_message(
+                            DefaultChannel::ReliableOrdered,
+                            postcard::to_allocvec(&announce_variant).unwrap(),
+                        );
                         let start_game = GameEvent::TurnChanged {
                             player_id: starting_player_id.unwrap(),
                         };
@@ -97,13 +350,23 @@ fn main() {
                             DefaultChannel::ReliableOrdered,
                             postcard::to_allocvec(&start_game).unwrap(),
                         );
-                        game_state =
-                            Some(GameState::new(players.clone(), starting_player_id.unwrap()));
+                        game_state = Some(GameState::new(
+                            players.clone(),
+                            starting_player_id.unwrap(),
+                            variant,
+                        ));
+                        if let Some(ballot) = &ballot
+                            && let Some(state) = &mut game_state
+                        {
+                            play_ballot(&mut server, state, ballot);
+                        }
                     }
                 }
                 ServerEvent::ClientDisconnected { client_id, reason } => {
                     info!("😢 Client disconnected! {client_id}, reason: {reason}");
-                    if let Some(state) = &mut game_state {
+                    players.remove(&client_id);
+                    // a saved game keeps waiting for its players
+                    if !resuming && let Some(state) = &mut game_state {
                         // a player disconnected
                         let disconnect_event = GameEvent::PlayerLeft {
                             player_id: client_id,
@@ -129,6 +392,7 @@ fn main() {
                             DefaultChannel::ReliableOrdered,
                             postcard::to_allocvec(&end_game).unwrap(),
                         );
+                        game_over = true;
                     }
                 }
             }
@@ -139,8 +403,13 @@ fn main() {
             {
                 match postcard::from_bytes::<GameEvent>(&bytes) {
                     Ok(msg) => {
-                        if let Some(state) = &mut game_state {
+                        if !resuming && let Some(state) = &mut game_state {
                             info!("ℹ️ Received from client {client_id} a message: {:?}", msg);
+                            // clients only act for themselves, the server creates the other events
+                            if msg.sender() != Some(client_id) {
+                                info!("🚫 Client {client_id} cannot send this event");
+                                continue;
+                            }
                             match state.dispatch(&msg) {
                                 Ok(_) => {
                                     info!(
@@ -149,16 +418,57 @@ fn main() {
                                     server
                                         .broadcast_message(DefaultChannel::ReliableOrdered, bytes);
 
-                                    if let Ok(msg) =
-                                        postcard::to_allocvec(&GameEvent::TurnChanged {
-                                            player_id: state.next_turn(),
-                                        })
+                                    // a move or an accepted draw can end the game, otherwise
+                                    // the turn passes after a move
+                                    let is_move = matches!(msg, GameEvent::Move { .. });
+                                    let can_end_game =
+                                        is_move || matches!(msg, GameEvent::DrawAnswered { .. });
+                                    if can_end_game && let Some(end_game) = state.check_end_game() {
+                                        info!("🏆 Game over! Broadcasting to players...");
+                                        if state.dispatch(&end_game).is_err() {
+                                            info!("❌ Error attempting to consume a EndGame event");
+                                        }
+                                        server.broadcast_message(
+                                            DefaultChannel::ReliableOrdered,
+                                            postcard::to_allocvec(&end_game).unwrap(),
+                                        );
+                                        game_over = true;
+                                    } else if is_move
+                                        && let Some(player_id) = state
+                                            .players
+                                            .keys()
+                                            .copied()
+                                            .find(|id| *id != state.is_turn)
                                     {
+                                        // dispatched too, so that it ends up in the game's log
+                                        let turn_changed = GameEvent::TurnChanged { player_id };
+                                        if state.dispatch(&turn_changed).is_err() {
+                                            info!(
+                                                "❌ Error attempting to consume a TurnChanged event"
+                                            );
+                                        }
                                         server.broadcast_message(
                                             DefaultChannel::ReliableOrdered,
-                                            msg,
+                                            postcard::to_allocvec(&turn_changed).unwrap(),
                                         );
                                         info!("🔄 Broadcasting change of turn to players...");
+
+                                        if let Some(end_game) = tablebases
+                                            .as_ref()
+                                            .and_then(|tablebases| adjudicate(state, tablebases))
+                                        {
+                                            info!("⚖️ Dead position, adjudicated a draw!");
+                                            if state.dispatch(&end_game).is_err() {
+                                                info!(
+                                                    "❌ Error attempting to consume a EndGame event"
+                                                );
+                                            }
+                                            server.broadcast_message(
+                                                DefaultChannel::ReliableOrdered,
+                                                postcard::to_allocvec(&end_game).unwrap(),
+                                            );
+                                            game_over = true;
+                                        }
                                     }
                                 }
                                 Err(err) => {
@@ -174,6 +484,17 @@ fn main() {
             }
         }
 
+        // the game is over: clients go back to their menu, and the server waits for a new one
+        if game_over {
+            info!("🧹 Cleaning up the finished game...");
+            if let Some(state) = &game_state {
+                archive_game(state);
+            }
+            game_state = None;
+            starting_player_id = None;
+            game_over = false;
+        }
+
         transport.send_packets(&mut server);
         std::thread::sleep(Duration::from_millis(16));
     }
@@ -0,0 +1,109 @@
+//! compares move generation cell by cell on `Board` with the one on `BitBoard`, that american
+//! boards use, in moves generated per second.
+//! run with `cargo bench -p store --bench movegen`
+use std::{
+    collections::HashMap,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use store::{
+    bitboard::BitBoard,
+    board::{Board, Position},
+    game_utils::{Move, legal_moves_by_cell},
+    player::{Player, PlayerId},
+    rules::Variant,
+};
+
+const GAMES: usize = 50;
+const ROUNDS: usize = 20;
+
+/// positions reached by playing pseudo-random games from the start, with the player to move
+fn sample_positions() -> Vec<(Board, PlayerId)> {
+    let players = HashMap::from([
+        (
+            1,
+            Player {
+                id: 1,
+                name: "bottom".to_string(),
+                direction: 1,
+                score: 0,
+            },
+        ),
+        (
+            2,
+            Player {
+                id: 2,
+                name: "top".to_string(),
+                direction: -1,
+                score: 0,
+            },
+        ),
+    ]);
+    let mut seed: u64 = 0x5eed;
+    let mut positions = vec![];
+    for _ in 0..GAMES {
+        let mut board = Board::new(&players, 1, Variant::American);
+        let mut turn = 1;
+        for _ in 0..80 {
+            let moves = board.legal_moves(turn);
+            if moves.is_empty() {
+                break;
+            }
+            positions.push((board.clone(), turn));
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let mv = &moves[(seed >> 33) as usize % moves.len()];
+            board.apply(mv).unwrap();
+            turn = if turn == 1 { 2 } else { 1 };
+        }
+    }
+    positions
+}
+
+fn bench<P>(
+    name: &str,
+    positions: &[(P, PlayerId)],
+    generate: impl Fn(&P, PlayerId) -> Vec<Move>,
+) -> f64 {
+    let mut generated = 0;
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for (position, turn) in positions {
+            generated += black_box(generate(position, *turn)).len();
+        }
+    }
+    let elapsed = start.elapsed().max(Duration::from_nanos(1));
+    let rate = generated as f64 / elapsed.as_secs_f64();
+    println!("{name:>9}: {generated} moves in {elapsed:?} ({rate:.0} moves/s)");
+    rate
+}
+
+fn main() {
+    let positions = sample_positions();
+    let bitboards = positions
+        .iter()
+        .map(|(board, turn)| (BitBoard::try_from(board).unwrap(), *turn))
+        .collect::<Vec<_>>();
+
+    // both representations have to agree before comparing their speed
+    for ((board, turn), (bitboard, _)) in positions.iter().zip(bitboards.iter()) {
+        let mut expected = legal_moves_by_cell(board, *turn);
+        let mut actual = bitboard.legal_moves(*turn);
+        expected.sort_by_key(|mv| format!("{mv:?}"));
+        actual.sort_by_key(|mv| format!("{mv:?}"));
+        assert_eq!(expected, actual, "move generation differs on {board:?}");
+    }
+
+    println!("move generation over {} positions", positions.len());
+    let cell_rate = bench("cells", &positions, legal_moves_by_cell);
+    let bitboard_rate = bench("BitBoard", &bitboards, BitBoard::legal_moves);
+    // what `Board` does for american checkers: converting to a bitboard first
+    let board_rate = bench("Board", &positions, Board::legal_moves);
+    println!(
+        "speedup: {:.1}x, {:.1}x through Board",
+        bitboard_rate / cell_rate,
+        board_rate / cell_rate
+    );
+}
@@ -0,0 +1,209 @@
+//! The three-move ballot of American checkers: the first three moves of a game are not chosen
+//! by the players but drawn at random from a list of openings, so that games don't all follow
+//! the few lines known to be safest.
+//!
+//! Openings are written one per line as on a standard board, black on squares 1 to 12 moving
+//! first, e.g. `9-13 22-18 10-15`; `#` starts a comment. The standard list is built in: the 174
+//! positions three moves can reach, without the openings dropping a piece. Another list, e.g.
+//! the official one of the American Checker Federation, can be loaded from such a file instead.
+use std::{fs, path::Path};
+
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+use crate::{
+    board::Board,
+    game_utils::{Move, legal_moves},
+    pdn::parse_move,
+    player::PlayerId,
+    rules::Variant,
+};
+
+/// where the server looks for the list of openings
+pub const BALLOT_FILE: &str = "ballot.txt";
+/// the moves each opening is made of
+pub const BALLOT_PLIES: usize = 3;
+
+const STANDARD_OPENINGS: &str = include_str!("openings.txt");
+
+// the players of the standard board the openings are written for, black moving first
+const WHITE: PlayerId = 1;
+const BLACK: PlayerId = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ballot {
+    /// the moves of each opening, as played on the standard board
+    openings: Vec<Vec<Move>>,
+}
+
+impl Ballot {
+    /// the openings built in, played unless another list is loaded
+    pub fn standard() -> Ballot {
+        Self::parse(STANDARD_OPENINGS).unwrap()
+    }
+    /// every legal sequence of three moves from the starting position
+    pub fn all_openings() -> Ballot {
+        let mut openings = vec![vec![]];
+        let mut turn = BLACK;
+        for _ in 0..BALLOT_PLIES {
+            openings = openings
+                .into_iter()
+                .flat_map(|opening: Vec<Move>| {
+                    let board = play(&opening);
+                    legal_moves(&board, turn).into_iter().map(move |mv| {
+                        let mut opening = opening.clone();
+                        opening.push(mv);
+                        opening
+                    })
+                })
+                .collect();
+            turn = if turn == BLACK { WHITE } else { BLACK };
+        }
+        Ballot { openings }
+    }
+
+    pub fn parse(text: &str) -> Result<Ballot, String> {
+        let mut openings = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut board = play(&[]);
+            let mut turn = BLACK;
+            let mut opening = vec![];
+            for token in line.split_whitespace() {
+                let mv = parse_move(&board, turn, token)
+                    .map_err(|err| format!("Line {}: {err}", number + 1))?;
+                board.apply(&mv)?;
+                opening.push(mv);
+                turn = if turn == BLACK { WHITE } else { BLACK };
+            }
+            if opening.len() != BALLOT_PLIES {
+                return Err(format!(
+                    "Line {}: expected {BALLOT_PLIES} moves, found {}",
+                    number + 1,
+                    opening.len()
+                ));
+            }
+            openings.push(opening);
+        }
+        if openings.is_empty() {
+            return Err("No openings in the ballot".to_string());
+        }
+        Ok(Ballot { openings })
+    }
+    pub fn load(path: &Path) -> Result<Ballot, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    pub fn len(&self) -> usize {
+        self.openings.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.openings.is_empty()
+    }
+
+    /// an opening at random, for a game on `board` where `first_player` moves first: when they
+    /// sit at the bottom, the opening is played on the rotated board
+    pub fn draw(&self, board: &Board, first_player: PlayerId) -> Vec<Move> {
+        let opening = &self.openings[SmallRng::from_entropy().gen_range(0..self.openings.len())];
+        if first_player == board.players()[0] {
+            opening
+                .iter()
+                .map(|mv| mv.map_coords(|coords| board.rotate(coords)))
+                .collect()
+        } else {
+            opening.clone()
+        }
+    }
+}
+
+/// the standard board after `moves`
+fn play(moves: &[Move]) -> Board {
+    let mut board = Board::starting_position(Variant::American, [WHITE, BLACK]);
+    for mv in moves {
+        board.apply(mv).unwrap();
+    }
+    board
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::player::Player;
+
+    use super::*;
+
+    #[test]
+    fn the_standard_openings_are_read() {
+        let ballot = Ballot::standard();
+        assert_eq!(ballot.len(), 174);
+        let all = Ballot::all_openings();
+        assert!(
+            ballot
+                .openings
+                .iter()
+                .all(|opening| all.openings.contains(opening))
+        );
+
+        let ballot =
+            Ballot::parse("# two openings\n9-13 22-18 10-15\n\n11-15 22-18 15x22 # a capture\n");
+        assert_eq!(ballot.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn illegal_or_malformed_lines_are_rejected() {
+        for text in [
+            // white moving first
+            "22-18 9-13 10-15",
+            // a pawn moving back
+            "9-13 22-18 13-9",
+            // a capture left out
+            "11-15 22-18 10-14",
+            "9-13 22-18",
+            "9-13 22-18 10-15 18-14",
+            "9-13 22-18 ten-15",
+            "9-13 22-18 10",
+        ] {
+            let err = Ballot::parse(&format!("9-13 22-18 10-15\n{text}")).unwrap_err();
+            assert!(err.starts_with("Line 2: "), "{text}: {err}");
+        }
+        assert!(Ballot::parse("# nothing but comments\n").is_err());
+    }
+
+    #[test]
+    fn draws_are_legal_when_the_first_player_sits_at_the_bottom() {
+        // the server seats the player moving first at the bottom, as `Board::new` does
+        let players = [(BLACK, 1), (WHITE, -1)]
+            .map(|(id, direction)| {
+                let name = id.to_string();
+                let player = Player {
+                    id,
+                    name,
+                    direction,
+                    score: 0,
+                };
+                (id, player)
+            })
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let board = Board::new(&players, BLACK, Variant::American);
+        assert_eq!(board.players()[0], BLACK);
+
+        let ballot = Ballot::standard();
+        for _ in 0..500 {
+            let opening = ballot.draw(&board, BLACK);
+            assert_eq!(opening.len(), BALLOT_PLIES);
+            let mut board = board.clone();
+            let mut turn = BLACK;
+            for mv in opening {
+                assert!(legal_moves(&board, turn).contains(&mv), "{mv:?}");
+                board.apply(&mv).unwrap();
+                turn = if turn == BLACK { WHITE } else { BLACK };
+            }
+        }
+    }
+}
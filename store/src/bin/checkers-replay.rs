@@ -0,0 +1,47 @@
+//! Steps through a recorded game, printing the board after each event:
+//! `cargo run --bin checkers-replay -- game.log [--step]`
+use std::io::{BufRead, stdin};
+
+use store::event_log::EventLog;
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("Usage: checkers-replay <event log> [--step]");
+        std::process::exit(2);
+    };
+    // with --step, waits for enter before each event
+    let step = args.iter().any(|arg| arg == "--step");
+
+    if let Err(err) = replay(path, step) {
+        eprintln!("❌ {err}");
+        std::process::exit(1);
+    }
+}
+
+fn replay(path: &str, step: bool) -> Result<(), String> {
+    let bytes = std::fs::read(path).map_err(|err| format!("Cannot read {path}: {err}"))?;
+    let log = EventLog::from_bytes(&bytes)?;
+    let mut state = log.initial_state()?;
+
+    println!("{} game, {} events", log.variant, log.events.len());
+    for player in log.players.iter() {
+        println!("player {}: {}", player.id, player.name);
+    }
+    println!("{}", log.initial_position);
+    println!("{}", state.grid);
+
+    let mut lines = stdin().lock().lines();
+    for (i, event) in log.events.iter().enumerate() {
+        if step && lines.next().is_none() {
+            break;
+        }
+        println!("#{i} {event:?}");
+        state
+            .dispatch(event)
+            .map_err(|err| format!("Event {i} cannot be replayed: {err}"))?;
+        println!("{} to move", state.is_turn);
+        println!("{}", state.grid);
+    }
+    Ok(())
+}
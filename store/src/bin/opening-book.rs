@@ -0,0 +1,64 @@
+//! Builds an opening book from a collection of games:
+//! `cargo run --release --bin opening-book -- games.pdn [--variant american] [--plies 16] [--output book.bin]`
+use std::path::PathBuf;
+
+use store::{
+    board::Board,
+    opening_book::{BOOK_FILE, DEFAULT_PLIES, OpeningBook},
+    pdn::notation,
+    rules::Variant,
+};
+
+const USAGE: &str =
+    "Usage: opening-book <games.pdn> [--variant american] [--plies 16] [--output book.bin]";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("❌ {err}\n{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let mut games = None;
+    let mut variant = Variant::American;
+    let mut plies = DEFAULT_PLIES;
+    let mut output = PathBuf::from(BOOK_FILE);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--variant" => variant = args.next().ok_or("Missing variant")?.parse()?,
+            "--plies" => {
+                let value = args.next().ok_or("Missing plies")?;
+                plies = value
+                    .parse()
+                    .map_err(|_| format!("Invalid plies: {value}"))?;
+            }
+            "--output" => output = PathBuf::from(args.next().ok_or("Missing output")?),
+            _ => games = Some(arg),
+        }
+    }
+    let games = games.ok_or("Missing games")?;
+
+    let text =
+        std::fs::read_to_string(&games).map_err(|err| format!("Cannot read {games}: {err}"))?;
+    let book = OpeningBook::from_pdn(&text, variant, plies)?;
+    book.save(&output)?;
+    println!("{} positions saved to {}", book.len(), output.display());
+
+    // the book moves from the starting position, whoever moves first
+    let board = Board::starting_position(variant, [1, 2]);
+    for player_id in board.players() {
+        for book_move in book.moves(&board, player_id) {
+            println!(
+                "{:<8} played {:>5} times, scoring {}",
+                notation(&board, &book_move.mv),
+                book_move.weight,
+                book_move
+                    .score()
+                    .map_or("-".to_string(), |score| format!("{:.0}%", score * 100.0))
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! Counts the positions reached after every sequence of moves of a given length:
+//! `cargo run --release --bin perft -- <depth> [--variant american] [--fen FEN] [--divide] [--bitboard]`
+//! Without `--fen`, counts from the starting position, with the bottom player moving first.
+use std::time::Instant;
+
+use store::{
+    bitboard::BitBoard,
+    board::{Board, Position},
+    pdn::notation,
+    perft::{divide, perft},
+    player::PlayerId,
+    rules::Variant,
+};
+
+const USAGE: &str = "Usage: perft <depth> [--variant american] [--fen FEN] [--divide] [--bitboard]";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("❌ {err}\n{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let mut depth = None;
+    let mut variant = Variant::default();
+    let mut fen = None;
+    let mut split = false;
+    let mut bitboard = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--variant" => variant = args.next().ok_or("Missing variant")?.parse()?,
+            "--fen" => fen = Some(args.next().ok_or("Missing FEN")?),
+            "--divide" => split = true,
+            "--bitboard" => bitboard = true,
+            _ => {
+                depth = Some(
+                    arg.parse::<usize>()
+                        .map_err(|_| format!("Invalid depth: {arg}"))?,
+                )
+            }
+        }
+    }
+    let depth = depth.ok_or("Missing depth")?;
+
+    let players = [1, 2];
+    let (board, turn) = match fen {
+        Some(fen) => Board::from_fen(&fen, variant, players)?,
+        None => (Board::starting_position(variant, players), players[0]),
+    };
+
+    if bitboard {
+        count(&BitBoard::try_from(&board)?, &board, turn, depth, split);
+    } else {
+        count(&board, &board, turn, depth, split);
+    }
+    Ok(())
+}
+
+/// runs perft on `position`, using `board` to write the moves down
+fn count<P: Position>(position: &P, board: &Board, turn: PlayerId, depth: usize, split: bool) {
+    let start = Instant::now();
+    let nodes = if split {
+        let moves = divide(position, turn, depth);
+        for (mv, nodes) in moves.iter() {
+            println!("{:<12} {nodes}", notation(board, mv));
+        }
+        moves.iter().map(|(_, nodes)| nodes).sum()
+    } else {
+        perft(position, turn, depth)
+    };
+    let elapsed = start.elapsed();
+    println!(
+        "perft({depth}) = {nodes} in {:.3}s ({:.0} nodes/s)",
+        elapsed.as_secs_f64(),
+        nodes as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+}
@@ -0,0 +1,82 @@
+//! Generates the endgame tablebases of American checkers, or looks a position up in them:
+//! `cargo run --release --bin tablebase -- generate <pieces> [--dir tablebases]`
+//! `cargo run --release --bin tablebase -- probe <FEN> [--dir tablebases]`
+use std::{path::PathBuf, time::Instant};
+
+use store::{
+    board::Board,
+    game_utils::legal_moves,
+    pdn::notation,
+    rules::Variant,
+    tablebase::{Outcome, TABLEBASES_DIR, Tablebases},
+};
+
+const USAGE: &str = "Usage: tablebase generate <pieces> [--dir tablebases] | tablebase probe <FEN> [--dir tablebases]";
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("❌ {err}\n{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let command = args.next().ok_or("Missing command")?;
+    let mut dir = PathBuf::from(TABLEBASES_DIR);
+    let mut operand = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dir" => dir = PathBuf::from(args.next().ok_or("Missing directory")?),
+            _ => operand = Some(arg),
+        }
+    }
+    let operand = operand.ok_or("Missing operand")?;
+
+    match command.as_str() {
+        "generate" => {
+            let pieces = operand
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid number of pieces: {operand}"))?;
+            let start = Instant::now();
+            let tablebases = Tablebases::generate_with(pieces, |material| {
+                println!(
+                    "{:<14} {:.1}s",
+                    material.file_name(),
+                    start.elapsed().as_secs_f64()
+                )
+            })?;
+            tablebases.save(&dir)?;
+            println!("saved to {}", dir.display());
+        }
+        "probe" => {
+            let tablebases = Tablebases::load(&dir)?;
+            let (board, turn) = Board::from_fen(&operand, Variant::American, [1, 2])?;
+            let outcome = tablebases.probe_board(&board, turn).ok_or(format!(
+                "Not in the tables, which have up to {} pieces",
+                tablebases.max_pieces()
+            ))?;
+            println!("{}", describe(outcome));
+            let opponent = if turn == 1 { 2 } else { 1 };
+            for mv in legal_moves(&board, turn) {
+                let mut child = board.clone();
+                child.apply(&mv)?;
+                let outcome = tablebases
+                    .probe_board(&child, opponent)
+                    .unwrap()
+                    .for_previous_mover();
+                println!("{:<12} {}", notation(&board, &mv), describe(outcome));
+            }
+        }
+        _ => return Err(format!("Unknown command: {command}")),
+    }
+    Ok(())
+}
+
+fn describe(outcome: Outcome) -> String {
+    match outcome {
+        Outcome::Win(plies) => format!("win in {plies} plies"),
+        Outcome::Loss(plies) => format!("loss in {plies} plies"),
+        Outcome::Draw => "draw".to_string(),
+    }
+}
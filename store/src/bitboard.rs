@@ -0,0 +1,446 @@
+use crate::{
+    board::{Board, Position},
+    coords::Coords,
+    game_utils::Move,
+    piece::{Piece, PieceType},
+    player::PlayerId,
+    rules::Variant,
+};
+
+// the 32 playable cells of an 8x8 board, read row by row from the top: bit `4 * y + k`
+// is the k-th playable cell of row y. Even rows start on x = 1, odd rows on x = 0
+const EVEN_ROWS: u32 = 0x0f0f_0f0f;
+const ODD_ROWS: u32 = 0xf0f0_f0f0;
+// first and last playable cell of each row
+const LEFT_COLUMN: u32 = 0x1111_1111;
+const RIGHT_COLUMN: u32 = 0x8888_8888;
+pub(crate) const TOP_ROW: u32 = 0x0000_000f;
+pub(crate) const BOTTOM_ROW: u32 = 0xf000_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Direction {
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+use Direction::*;
+
+impl Direction {
+    pub(crate) const ALL: [Direction; 4] = [UpLeft, UpRight, DownLeft, DownRight];
+
+    pub(crate) fn is_up(self) -> bool {
+        matches!(self, UpLeft | UpRight)
+    }
+    pub(crate) fn opposite(self) -> Direction {
+        match self {
+            UpLeft => DownRight,
+            UpRight => DownLeft,
+            DownLeft => UpRight,
+            DownRight => UpLeft,
+        }
+    }
+    /// moves every bit of `bits` one cell in this direction, dropping the ones leaving the board.
+    /// the shift depends on the row parity: even rows are offset one cell to the right
+    pub(crate) fn shift(self, bits: u32) -> u32 {
+        match self {
+            UpLeft => ((bits & EVEN_ROWS) >> 4) | ((bits & ODD_ROWS & !LEFT_COLUMN) >> 5),
+            UpRight => ((bits & EVEN_ROWS & !RIGHT_COLUMN) >> 3) | ((bits & ODD_ROWS) >> 4),
+            DownLeft => ((bits & EVEN_ROWS) << 4) | ((bits & ODD_ROWS & !LEFT_COLUMN) << 3),
+            DownRight => ((bits & EVEN_ROWS & !RIGHT_COLUMN) << 5) | ((bits & ODD_ROWS) << 4),
+        }
+    }
+}
+
+fn square_to_coords(square: u32) -> Coords {
+    let y = (square / 4) as usize;
+    let k = (square % 4) as usize;
+    Coords {
+        x: 2 * k + (y + 1) % 2,
+        y,
+    }
+}
+fn coords_to_square(coords: Coords) -> Option<u32> {
+    if coords.x >= 8 || coords.y >= 8 || (coords.x + coords.y).is_multiple_of(2) {
+        return None;
+    }
+    Some((coords.y * 4 + coords.x / 2) as u32)
+}
+
+/// iterates over the set bits of `bits`, as single-bit masks
+pub(crate) fn bits(mut bits: u32) -> impl Iterator<Item = u32> {
+    std::iter::from_fn(move || {
+        if bits == 0 {
+            return None;
+        }
+        let bit = bits & bits.wrapping_neg();
+        bits ^= bit;
+        Some(bit)
+    })
+}
+
+/// an American checkers board stored as bitmasks over its 32 playable cells, generating the
+/// same moves as `Board` without allocating anything but the moves themselves:
+/// `game_utils::legal_moves` goes through it for american boards
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitBoard {
+    /// pieces of the player at the bottom of the board, moving up
+    pub bottom: u32,
+    /// pieces of the player at the top of the board, moving down
+    pub top: u32,
+    pub kings: u32,
+    players: [PlayerId; 2],
+}
+impl BitBoard {
+    pub fn new(bottom: u32, top: u32, kings: u32, players: [PlayerId; 2]) -> Self {
+        BitBoard {
+            bottom,
+            top,
+            kings,
+            players,
+        }
+    }
+    pub fn players(&self) -> [PlayerId; 2] {
+        self.players
+    }
+    /// the same position seen from the other side of the board: the squares are numbered
+    /// backwards, and the players swap places
+    pub fn rotated(&self) -> Self {
+        BitBoard {
+            bottom: self.top.reverse_bits(),
+            top: self.bottom.reverse_bits(),
+            kings: self.kings.reverse_bits(),
+            players: [self.players[1], self.players[0]],
+        }
+    }
+    pub fn empty(&self) -> u32 {
+        !(self.bottom | self.top)
+    }
+
+    // own pieces, opponent pieces, and whether the player moves up
+    fn sides(&self, player_id: PlayerId) -> (u32, u32, bool) {
+        if player_id == self.players[0] {
+            (self.bottom, self.top, true)
+        } else {
+            (self.top, self.bottom, false)
+        }
+    }
+    fn directions(is_king: bool, moves_up: bool) -> impl Iterator<Item = Direction> {
+        Direction::ALL
+            .into_iter()
+            .filter(move |dir| is_king || dir.is_up() == moves_up)
+    }
+
+    /// pieces of the player that can capture something right away
+    pub fn jumpers(&self, player_id: PlayerId) -> u32 {
+        let (own, opponent, moves_up) = self.sides(player_id);
+        let empty = self.empty();
+        Direction::ALL
+            .into_iter()
+            .map(|dir| {
+                let movers = if dir.is_up() == moves_up {
+                    own
+                } else {
+                    own & self.kings
+                };
+                let back = dir.opposite();
+                back.shift(back.shift(empty) & opponent) & movers
+            })
+            .fold(0, |acc, jumpers| acc | jumpers)
+    }
+
+    fn capture_sequences(
+        &self,
+        player_id: PlayerId,
+        path: &mut Vec<u32>,
+        eaten: &mut Vec<u32>,
+        moves: &mut Vec<Move>,
+    ) {
+        let (_, opponent, moves_up) = self.sides(player_id);
+        let origin = path[0];
+        let current = *path.last().unwrap();
+        let is_king = self.kings & origin != 0;
+        let captured = eaten.iter().fold(0, |acc, bit| acc | bit);
+        // the moving piece already left its original cell, captured pieces stay until the end
+        let empty = self.empty() | origin;
+        let promotion_row = if moves_up { TOP_ROW } else { BOTTOM_ROW };
+        let mut extended = false;
+
+        for dir in Self::directions(is_king, moves_up) {
+            let edible = dir.shift(current) & opponent & !captured;
+            let landing = dir.shift(edible) & empty;
+            if landing == 0 {
+                continue;
+            }
+            extended = true;
+            path.push(landing);
+            eaten.push(edible);
+
+            // rule: a pawn crowned mid-sequence stops there
+            if !is_king && landing & promotion_row != 0 {
+                moves.push(Self::to_capture(path, eaten));
+            } else {
+                self.capture_sequences(player_id, path, eaten, moves);
+            }
+
+            path.pop();
+            eaten.pop();
+        }
+
+        if !extended && !eaten.is_empty() {
+            moves.push(Self::to_capture(path, eaten));
+        }
+    }
+    fn to_capture(path: &[u32], eaten: &[u32]) -> Move {
+        let to_coords = |bit: &u32| square_to_coords(bit.trailing_zeros());
+        Move::Capture {
+            path: path.iter().map(to_coords).collect(),
+            eat: eaten.iter().map(to_coords).collect(),
+        }
+    }
+}
+impl Position for BitBoard {
+    fn piece_at(&self, coords: Coords) -> Option<Piece> {
+        let bit = 1 << coords_to_square(coords)?;
+        let piece_type = if self.kings & bit != 0 {
+            PieceType::King
+        } else {
+            PieceType::Pawn
+        };
+        if self.bottom & bit != 0 {
+            Some(Piece::new(piece_type, self.players[0]))
+        } else if self.top & bit != 0 {
+            Some(Piece::new(piece_type, self.players[1]))
+        } else {
+            None
+        }
+    }
+
+    fn legal_moves(&self, player_id: PlayerId) -> Vec<Move> {
+        let (own, _, moves_up) = self.sides(player_id);
+        let mut moves = vec![];
+
+        // rule: forced to capture if any piece can capture
+        let jumpers = self.jumpers(player_id);
+        if jumpers != 0 {
+            for piece in bits(jumpers) {
+                self.capture_sequences(player_id, &mut vec![piece], &mut vec![], &mut moves);
+            }
+            return moves;
+        }
+
+        let empty = self.empty();
+        for dir in Direction::ALL {
+            let movers = if dir.is_up() == moves_up {
+                own
+            } else {
+                own & self.kings
+            };
+            for to in bits(dir.shift(movers) & empty) {
+                moves.push(Move::Simple {
+                    from: square_to_coords(dir.opposite().shift(to).trailing_zeros()),
+                    to: square_to_coords(to.trailing_zeros()),
+                });
+            }
+        }
+        moves
+    }
+
+    fn apply(&mut self, mv: &Move) -> Result<(), String> {
+        let square = |coords: Coords| {
+            coords_to_square(coords)
+                .map(|square| 1u32 << square)
+                .ok_or(format!("{:?} is not a playable cell", coords))
+        };
+        let from = square(mv.from())?;
+        let to = square(mv.to())?;
+        let (side, promotion_row) = if self.bottom & from != 0 {
+            (&mut self.bottom, TOP_ROW)
+        } else if self.top & from != 0 {
+            (&mut self.top, BOTTOM_ROW)
+        } else {
+            return Err(format!("No piece to move in {:?}", mv.from()));
+        };
+        *side = (*side & !from) | to;
+        if self.kings & from != 0 || to & promotion_row != 0 {
+            self.kings = (self.kings & !from) | to;
+        }
+
+        for eaten in mv.eaten() {
+            let bit = square(*eaten)?;
+            self.bottom &= !bit;
+            self.top &= !bit;
+            self.kings &= !bit;
+        }
+        Ok(())
+    }
+    fn players(&self) -> [PlayerId; 2] {
+        self.players
+    }
+}
+impl TryFrom<&Board> for BitBoard {
+    type Error = String;
+
+    fn try_from(board: &Board) -> Result<Self, Self::Error> {
+        if board.variant() != Variant::American {
+            return Err(format!(
+                "Bitboards only play american checkers, not {}",
+                board.variant()
+            ));
+        }
+        let players = board.players();
+        let mut bitboard = BitBoard {
+            bottom: 0,
+            top: 0,
+            kings: 0,
+            players,
+        };
+        for square in 0..32 {
+            let bit = 1 << square;
+            let Some(piece) = board[square_to_coords(square)] else {
+                continue;
+            };
+            if piece.player_id == players[0] {
+                bitboard.bottom |= bit;
+            } else {
+                bitboard.top |= bit;
+            }
+            if piece.is_king() {
+                bitboard.kings |= bit;
+            }
+        }
+        Ok(bitboard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{SeedableRng, rngs::SmallRng, seq::SliceRandom};
+
+    use super::*;
+    use crate::game_utils::legal_moves_by_cell;
+
+    const PLAYERS: [PlayerId; 2] = [1, 2];
+
+    fn position(fen: &str) -> (Board, BitBoard, PlayerId) {
+        let (board, turn) = Board::from_fen(fen, Variant::American, PLAYERS).unwrap();
+        let bitboard = BitBoard::try_from(&board).unwrap();
+        (board, bitboard, turn)
+    }
+    fn sorted(mut moves: Vec<Move>) -> Vec<Move> {
+        moves.sort_by_key(|mv| format!("{mv:?}"));
+        moves
+    }
+
+    #[test]
+    fn shifts_follow_the_diagonals() {
+        let steps = [
+            (UpLeft, -1, -1),
+            (UpRight, 1, -1),
+            (DownLeft, -1, 1),
+            (DownRight, 1, 1),
+        ];
+        for square in 0..32 {
+            let coords = square_to_coords(square);
+            assert_eq!(coords_to_square(coords), Some(square));
+            for (dir, dx, dy) in steps {
+                let expected = coords
+                    .step(dx, dy, 8, 8)
+                    .and_then(coords_to_square)
+                    .map_or(0, |square| 1 << square);
+                assert_eq!(dir.shift(1 << square), expected, "{dir:?} from {coords:?}");
+                if expected != 0 {
+                    assert_eq!(dir.opposite().shift(expected), 1 << square);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn edge_masks_hold_the_edges() {
+        for square in 0..32 {
+            let bit = 1 << square;
+            let Coords { x, y } = square_to_coords(square);
+            assert_eq!(LEFT_COLUMN & bit != 0, x <= 1, "{x},{y}");
+            assert_eq!(RIGHT_COLUMN & bit != 0, x >= 6, "{x},{y}");
+            assert_eq!(TOP_ROW & bit != 0, y == 0, "{x},{y}");
+            assert_eq!(BOTTOM_ROW & bit != 0, y == 7, "{x},{y}");
+            assert_eq!(EVEN_ROWS & bit != 0, y % 2 == 0, "{x},{y}");
+        }
+        assert_eq!(EVEN_ROWS | ODD_ROWS, u32::MAX);
+        // nothing leaves the board
+        assert_eq!(UpLeft.shift(TOP_ROW) | UpRight.shift(TOP_ROW), 0);
+        assert_eq!(DownLeft.shift(BOTTOM_ROW) | DownRight.shift(BOTTOM_ROW), 0);
+    }
+
+    #[test]
+    fn pawns_are_crowned_on_the_last_row() {
+        let (_, bitboard, turn) = position("W:W6:B27");
+        for mv in bitboard.legal_moves(turn) {
+            let mut crowned = bitboard;
+            crowned.apply(&mv).unwrap();
+            assert!(crowned.piece_at(mv.to()).unwrap().is_king());
+        }
+        let (_, mut bitboard, _) = position("B:W6:B27");
+        let mv = bitboard.legal_moves(PLAYERS[1]).pop().unwrap();
+        bitboard.apply(&mv).unwrap();
+        assert!(bitboard.piece_at(mv.to()).unwrap().is_king());
+        assert_eq!(bitboard.kings.count_ones(), 1);
+    }
+
+    #[test]
+    fn crowning_ends_a_capture() {
+        let (board, bitboard, turn) = position("W:W11:B6,7");
+        let moves = bitboard.legal_moves(turn);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].eaten().len(), 1);
+        assert_eq!(sorted(moves), sorted(legal_moves_by_cell(&board, turn)));
+    }
+
+    #[test]
+    fn kings_capture_in_every_direction() {
+        let (board, bitboard, turn) = position("W:WK22:B17,18,26,25,10");
+        let moves = bitboard.legal_moves(turn);
+        assert!(moves.iter().any(|mv| mv.eaten().len() > 1));
+        assert_eq!(sorted(moves), sorted(legal_moves_by_cell(&board, turn)));
+    }
+
+    #[test]
+    fn moves_match_the_board_over_random_games() {
+        let mut rng = SmallRng::seed_from_u64(0xb17);
+        let mut multi_captures = 0;
+        for _ in 0..100 {
+            let mut board = Board::starting_position(Variant::American, PLAYERS);
+            let mut bitboard = BitBoard::try_from(&board).unwrap();
+            let mut turn = PLAYERS[0];
+            for _ in 0..150 {
+                let moves = sorted(bitboard.legal_moves(turn));
+                assert_eq!(moves, sorted(legal_moves_by_cell(&board, turn)), "{board}");
+                multi_captures += moves.iter().filter(|mv| mv.eaten().len() > 1).count();
+                let Some(mv) = moves.choose(&mut rng) else {
+                    break;
+                };
+                board.apply(mv).unwrap();
+                bitboard.apply(mv).unwrap();
+                assert_eq!(bitboard, BitBoard::try_from(&board).unwrap(), "{board}");
+                turn = if turn == PLAYERS[0] {
+                    PLAYERS[1]
+                } else {
+                    PLAYERS[0]
+                };
+            }
+        }
+        assert!(multi_captures > 0);
+    }
+
+    #[test]
+    fn rotating_swaps_the_sides() {
+        let (board, bitboard, _) = position("W:W21,K30:B1,K9,10");
+        assert_eq!(
+            bitboard.rotated(),
+            BitBoard::try_from(&board.rotated()).unwrap()
+        );
+        assert_eq!(bitboard.rotated().rotated(), bitboard);
+        assert!(BitBoard::try_from(&Board::empty(Variant::Russian, PLAYERS)).is_err());
+    }
+}
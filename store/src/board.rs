@@ -1,60 +1,378 @@
-use std::{
-    collections::HashMap,
-    ops::{Index, IndexMut},
-};
+use std::{collections::HashMap, fmt, ops::Index};
+
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    CELL_N,
     coords::Coords,
-    game_utils::is_white,
+    game_utils::{Move, coords_to_index, legal_moves, promotes},
     piece::{Piece, PieceType},
     player::{Player, PlayerId},
+    rules::{RuleSet, Variant},
+    zobrist,
 };
 
-#[derive(Debug, Clone)]
-pub struct Board(Vec<Vec<Option<Piece>>>);
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Board {
+    cells: Vec<Vec<Option<Piece>>>,
+    pub width: usize,
+    pub height: usize,
+    /// rows filled with pawns for each player at the start of the game
+    pub starting_rows: usize,
+    // the starting player sits at the bottom of the board and moves up, towards row 0
+    bottom_player: PlayerId,
+    top_player: PlayerId,
+    variant: Variant,
+    // zobrist hash of the pieces, kept up to date by `set`
+    hash: u64,
+}
 impl Board {
-    pub fn new(players: &HashMap<PlayerId, Player>, starting_turn: PlayerId) -> Self {
-        let mut grid = vec![vec![None; CELL_N]; CELL_N];
+    pub fn new(
+        players: &HashMap<PlayerId, Player>,
+        starting_turn: PlayerId,
+        variant: Variant,
+    ) -> Self {
         // let player1: &Player = players.get(&starting_turn).unwrap();
         let player2: &Player = players
             .iter()
-            .filter(|player| *player.0 != starting_turn)
-            .next()
+            .find(|player| *player.0 != starting_turn)
             .unwrap()
             .1;
+        Board::starting_position(variant, [starting_turn, player2.id])
+    }
+
+    /// the board at the start of a game; `players` are the bottom and the top player
+    pub fn starting_position(variant: Variant, players: [PlayerId; 2]) -> Self {
+        let mut board = Board::empty(variant, players);
 
-        for i in 0..CELL_N {
-            for j in 0..CELL_N {
-                let coords = Coords { x: i, y: j };
-                if i < 3 && is_white(coords) {
-                    grid[i][j] = Some(Piece {
+        for coords in board.coords() {
+            if !board.is_playable(coords) {
+                continue;
+            }
+            if coords.y < board.starting_rows {
+                board.set(
+                    coords,
+                    Some(Piece {
                         piece_type: PieceType::Pawn,
-                        player_id: player2.id,
-                    });
-                } else if i > 4 && is_white(coords) {
-                    grid[i][j] = Some(Piece {
+                        player_id: players[1],
+                    }),
+                );
+            } else if coords.y >= board.height - board.starting_rows {
+                board.set(
+                    coords,
+                    Some(Piece {
                         piece_type: PieceType::Pawn,
-                        player_id: starting_turn,
-                    });
-                } else {
-                    grid[i][j] = None;
+                        player_id: players[0],
+                    }),
+                );
+            }
+        }
+        board
+    }
+
+    /// a board without any piece; `players` are the bottom and the top player
+    pub fn empty(variant: Variant, players: [PlayerId; 2]) -> Self {
+        let rules = variant.rules();
+        let (width, height) = (rules.board_size(), rules.board_size());
+        Board {
+            cells: vec![vec![None; width]; height],
+            width,
+            height,
+            starting_rows: rules.starting_rows(),
+            bottom_player: players[0],
+            top_player: players[1],
+            variant,
+            hash: 0,
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+    pub fn rules(&self) -> &'static dyn RuleSet {
+        self.variant.rules()
+    }
+    pub fn is_playable(&self, coords: Coords) -> bool {
+        self.rules().is_playable(coords)
+    }
+    /// the zobrist hash of the pieces on the board, see `zobrist::position_hash`
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+    /// puts `piece` on the cell, or empties it, returning what was there before
+    pub fn set(&mut self, coords: Coords, piece: Option<Piece>) -> Option<Piece> {
+        let cell = coords_to_index(coords, self.width);
+        let previous = std::mem::replace(&mut self.cells[coords.y][coords.x], piece);
+        for piece in [previous, piece].into_iter().flatten() {
+            self.hash ^= zobrist::piece_key(cell, piece, piece.player_id == self.bottom_player);
+        }
+        previous
+    }
+
+    /// 1 if the player's pawns move up the board (towards row 0), -1 if they move down
+    pub fn direction(&self, player_id: PlayerId) -> i32 {
+        if player_id == self.bottom_player {
+            1
+        } else {
+            -1
+        }
+    }
+
+    /// all the cells of the board, row by row
+    pub fn coords(&self) -> impl Iterator<Item = Coords> + use<> {
+        let (width, height) = (self.width, self.height);
+        (0..height).flat_map(move |y| (0..width).map(move |x| Coords { x, y }))
+    }
+    pub fn contains(&self, coords: Coords) -> bool {
+        coords.x < self.width && coords.y < self.height
+    }
+    /// the neighbouring cell in direction (dx, dy), if it is still on the board
+    pub fn step(&self, coords: Coords, dx: i32, dy: i32) -> Option<Coords> {
+        coords.step(dx, dy, self.width, self.height)
+    }
+    /// the playable cells numbered from 1, row by row from the top left, as in draughts notation
+    pub fn square_number(&self, coords: Coords) -> Option<usize> {
+        if !self.contains(coords) || !self.is_playable(coords) {
+            return None;
+        }
+        Some(coords.y * self.width.div_ceil(2) + coords.x / 2 + 1)
+    }
+    pub fn square_coords(&self, number: usize) -> Option<Coords> {
+        let per_row = self.width.div_ceil(2);
+        let index = number.checked_sub(1)?;
+        let y = index / per_row;
+        // every row holds one playable cell out of two, starting on the first or second column
+        let x = (0..2)
+            .map(|offset| 2 * (index % per_row) + offset)
+            .find(|x| self.is_playable(Coords { x: *x, y }))?;
+        let coords = Coords { x, y };
+        self.contains(coords).then_some(coords)
+    }
+    /// the two players, the one at the bottom of the board first
+    pub fn players(&self) -> [PlayerId; 2] {
+        [self.bottom_player, self.top_player]
+    }
+    /// gives the pieces and the side of player `from` to player `to`
+    pub fn reseat(&mut self, from: PlayerId, to: PlayerId) {
+        for row in self.cells.iter_mut() {
+            for piece in row.iter_mut().flatten() {
+                if piece.player_id == from {
+                    piece.player_id = to;
                 }
             }
         }
+        for player_id in [&mut self.bottom_player, &mut self.top_player] {
+            if *player_id == from {
+                *player_id = to;
+            }
+        }
+    }
+
+    /// the same position seen from the other side of the board, the players swapping places
+    pub fn rotated(&self) -> Board {
+        let mut board = Board::empty(self.variant, [self.top_player, self.bottom_player]);
+        for coords in self.coords() {
+            if let Some(piece) = self[coords] {
+                board.set(self.rotate(coords), Some(piece));
+            }
+        }
+        board
+    }
+    /// where the cell is on the rotated board
+    pub fn rotate(&self, coords: Coords) -> Coords {
+        Coords {
+            x: self.width - 1 - coords.x,
+            y: self.height - 1 - coords.y,
+        }
+    }
+
+    /// moves the piece, crowning it if needed, and removes the captured ones
+    pub fn apply(&mut self, mv: &Move) -> Result<(), String> {
+        self.play(mv).map(|_| ())
+    }
+    /// like `apply`, but keeps track of what changed so that the move can be taken back
+    pub fn play(&mut self, mv: &Move) -> Result<MoveRecord, String> {
+        let crowned = promotes(self, mv);
+
+        // remove selected pawn from prev cell
+        let Some(mut piece) = self.set(mv.from(), None) else {
+            return Err(format!("No piece to move in {:?}", mv.from()));
+        };
+
+        // crown the pawn if it reached the opponent's back row
+        if crowned {
+            piece.piece_type = PieceType::King;
+        }
+        self.set(mv.to(), Some(piece));
+
+        // eat if thats the case
+        let mut captured = vec![];
+        for eaten in mv.eaten() {
+            if let Some(piece) = self.set(*eaten, None) {
+                captured.push((*eaten, piece));
+            }
+        }
+        Ok(MoveRecord {
+            mv: mv.clone(),
+            captured,
+            crowned,
+        })
+    }
+    /// takes back the last move played, putting the captured pieces back
+    pub fn undo(&mut self, record: &MoveRecord) -> Result<(), String> {
+        let mv = &record.mv;
+        let Some(mut piece) = self.set(mv.to(), None) else {
+            return Err(format!("No piece to move back from {:?}", mv.to()));
+        };
+        if record.crowned {
+            piece.piece_type = PieceType::Pawn;
+        }
+        self.set(mv.from(), Some(piece));
+
+        for (coords, piece) in record.captured.iter() {
+            self.set(*coords, Some(*piece));
+        }
+        Ok(())
+    }
+}
 
-        Board(grid)
+/// what a move changed on the board, enough to take it back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MoveRecord {
+    pub mv: Move,
+    /// the captured pieces, where they stood
+    pub captured: Vec<(Coords, Piece)>,
+    /// whether the moving pawn got crowned
+    pub crowned: bool,
+}
+
+/// what games and searches need from a board, whatever its representation
+pub trait Position: Clone {
+    fn piece_at(&self, coords: Coords) -> Option<Piece>;
+    /// every legal move of the player, see `game_utils::legal_moves`
+    fn legal_moves(&self, player_id: PlayerId) -> Vec<Move>;
+    fn apply(&mut self, mv: &Move) -> Result<(), String>;
+    /// the two players, the one at the bottom of the board first
+    fn players(&self) -> [PlayerId; 2];
+}
+impl Position for Board {
+    fn piece_at(&self, coords: Coords) -> Option<Piece> {
+        self[coords]
+    }
+    fn legal_moves(&self, player_id: PlayerId) -> Vec<Move> {
+        legal_moves(self, player_id)
+    }
+    fn apply(&mut self, mv: &Move) -> Result<(), String> {
+        Board::apply(self, mv)
+    }
+    fn players(&self) -> [PlayerId; 2] {
+        Board::players(self)
+    }
+}
+/// the board as text, one row per line: `w` and `b` for the bottom and top player's pawns,
+/// uppercase for kings, `.` for empty playable cells
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..self.height {
+            let row = (0..self.width)
+                .map(|x| {
+                    let coords = Coords { x, y };
+                    match self[coords] {
+                        Some(piece) => {
+                            let symbol = if piece.player_id == self.bottom_player {
+                                'w'
+                            } else {
+                                'b'
+                            };
+                            if piece.is_king() {
+                                symbol.to_ascii_uppercase()
+                            } else {
+                                symbol
+                            }
+                        }
+                        None if self.is_playable(coords) => '.',
+                        None => ' ',
+                    }
+                })
+                .map(String::from)
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(f, "{row}")?;
+        }
+        Ok(())
     }
 }
 impl Index<Coords> for Board {
     type Output = Option<Piece>;
 
     fn index(&self, index: Coords) -> &Self::Output {
-        &self.0[index.y][index.x]
+        &self.cells[index.y][index.x]
     }
 }
-impl IndexMut<Coords> for Board {
-    fn index_mut(&mut self, index: Coords) -> &mut Self::Output {
-        &mut self.0[index.y][index.x]
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYERS: [PlayerId; 2] = [1, 2];
+
+    #[test]
+    fn boards_take_their_size_from_the_variant() {
+        for variant in Variant::ALL {
+            let size = variant.rules().board_size();
+            let board = Board::starting_position(variant, PLAYERS);
+            assert_eq!((board.width, board.height), (size, size), "{variant}");
+            for player_id in PLAYERS {
+                let pieces = board
+                    .coords()
+                    .filter(|coords| board[*coords].is_some_and(|p| p.player_id == player_id))
+                    .count();
+                assert_eq!(pieces, board.starting_rows * size / 2, "{variant}");
+            }
+            assert!(board.coords().all(|coords| board.contains(coords)));
+            assert!(!board.contains(Coords { x: size, y: 0 }));
+        }
+    }
+
+    #[test]
+    fn squares_are_numbered_on_every_board_size() {
+        for variant in Variant::ALL {
+            let board = Board::empty(variant, PLAYERS);
+            let numbers = board
+                .coords()
+                .filter_map(|coords| {
+                    let number = board.square_number(coords)?;
+                    assert_eq!(board.square_coords(number), Some(coords), "{variant}");
+                    Some(number)
+                })
+                .collect::<Vec<_>>();
+            let squares = board.width * board.height / 2;
+            assert_eq!(numbers, (1..=squares).collect::<Vec<_>>(), "{variant}");
+            assert_eq!(board.square_coords(squares + 1), None, "{variant}");
+        }
+    }
+
+    #[test]
+    fn pieces_move_across_larger_boards() {
+        // the front pawns of each side, one move each way but on the edges
+        let international = Board::starting_position(Variant::International, PLAYERS);
+        assert_eq!(legal_moves(&international, PLAYERS[0]).len(), 9);
+        let canadian = Board::starting_position(Variant::Canadian, PLAYERS);
+        assert_eq!(legal_moves(&canadian, PLAYERS[0]).len(), 11);
+
+        // a flying king along the whole long diagonal of the 10x10 board
+        let (board, turn) = Board::from_fen("W:WK46:B1", Variant::International, PLAYERS).unwrap();
+        assert_eq!(board.square_coords(46), Some(Coords { x: 0, y: 9 }));
+        assert_eq!(legal_moves(&board, turn).len(), 9);
+    }
+
+    #[test]
+    fn rotating_swaps_the_players() {
+        let board = Board::starting_position(Variant::International, PLAYERS);
+        let rotated = board.rotated();
+        assert_eq!(rotated.players(), [PLAYERS[1], PLAYERS[0]]);
+        for coords in board.coords() {
+            assert_eq!(rotated[board.rotate(coords)], board[coords]);
+        }
+        assert_eq!(rotated.rotated(), board);
     }
 }
@@ -2,8 +2,6 @@ use std::ops::{Add, Sub};
 
 use serde::{Deserialize, Serialize};
 
-use crate::CELL_N;
-
 #[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Coords {
     pub x: usize,
@@ -33,7 +31,7 @@ impl Coords {
     // very verbose and not scalable, but for a simple game with only 4 diagonals it is fine.
     // this lets me avoid many casting or using checked_sub as im working with usize and i32
     // and arithmetic is annoying
-    pub fn diag(self) -> Vec<Coords> {
+    pub fn diag(self, width: usize, height: usize) -> Vec<Coords> {
         let mut v = Vec::new();
 
         // Top-left
@@ -44,21 +42,21 @@ impl Coords {
             });
         }
         // Top-right
-        if self.x < CELL_N - 1 && self.y > 0 {
+        if self.x < width - 1 && self.y > 0 {
             v.push(Coords {
                 x: self.x + 1,
                 y: self.y - 1,
             });
         }
         // Bottom-left
-        if self.x > 0 && self.y < CELL_N - 1 {
+        if self.x > 0 && self.y < height - 1 {
             v.push(Coords {
                 x: self.x - 1,
                 y: self.y + 1,
             });
         }
         // Bottom-right
-        if self.x < CELL_N - 1 && self.y < CELL_N - 1 {
+        if self.x < width - 1 && self.y < height - 1 {
             v.push(Coords {
                 x: self.x + 1,
                 y: self.y + 1,
@@ -67,4 +65,17 @@ impl Coords {
 
         v
     }
+
+    /// the neighbouring cell in direction (dx, dy), if it is still on a `width` x `height` board
+    pub fn step(self, dx: i32, dy: i32, width: usize, height: usize) -> Option<Coords> {
+        let x = self.x as i32 + dx;
+        let y = self.y as i32 + dy;
+        if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+            return None;
+        }
+        Some(Coords {
+            x: x as usize,
+            y: y as usize,
+        })
+    }
 }
@@ -0,0 +1,250 @@
+//! Recorded games: the starting position, the players and every event dispatched to the game,
+//! enough to rebuild the exact same `GameState` with `GameState::replay`.
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::Board,
+    game_state::{GameEvent, GameState},
+    player::Player,
+    rules::Variant,
+};
+
+/// bumped whenever the log or `GameEvent` change in a way older logs can't be read with
+pub const EVENT_LOG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventLog {
+    pub version: u32,
+    pub variant: Variant,
+    /// the players when the game started, the bottom one first
+    pub players: Vec<Player>,
+    /// the starting position as FEN, white being the bottom player
+    pub initial_position: String,
+    pub events: Vec<GameEvent>,
+}
+
+impl EventLog {
+    pub fn from_game(state: &GameState) -> EventLog {
+        let (board, is_turn) = state.initial_position();
+        let players = board
+            .players()
+            .iter()
+            .filter_map(|player_id| state.initial_players().get(player_id).cloned())
+            .collect();
+        EventLog {
+            version: EVENT_LOG_VERSION,
+            variant: board.variant(),
+            players,
+            initial_position: board.to_fen(is_turn),
+            events: state.history().to_vec(),
+        }
+    }
+
+    /// the game as it was before the first event
+    pub fn initial_state(&self) -> Result<GameState, String> {
+        let [bottom, top] = match &self.players[..] {
+            [bottom, top] => [bottom.id, top.id],
+            _ => return Err(format!("Expected 2 players, found {}", self.players.len())),
+        };
+        let (board, is_turn) =
+            Board::from_fen(&self.initial_position, self.variant, [bottom, top])?;
+        let players = self
+            .players
+            .iter()
+            .map(|player| (player.id, player.clone()))
+            .collect();
+        Ok(GameState::from_position(players, board, is_turn))
+    }
+
+    pub fn replay(&self) -> Result<GameState, String> {
+        GameState::replay(self.initial_state()?, &self.events)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        postcard::to_allocvec(self).map_err(|err| format!("Cannot serialize the log: {err}"))
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Result<EventLog, String> {
+        // the version comes first, so that it can be checked before reading the rest
+        let (version, _) = postcard::take_from_bytes::<u32>(bytes)
+            .map_err(|err| format!("Not an event log: {err}"))?;
+        if version != EVENT_LOG_VERSION {
+            return Err(format!(
+                "Unsupported event log version {version}, expected {EVENT_LOG_VERSION}"
+            ));
+        }
+        postcard::from_bytes(bytes).map_err(|err| format!("Corrupted event log: {err}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{
+        game_state::EndGameReason,
+        game_utils::legal_moves,
+        player::{Player, PlayerId},
+    };
+
+    fn new_game(ids: [PlayerId; 2]) -> GameState {
+        let players = ids
+            .into_iter()
+            .zip([1, -1])
+            .map(|(id, direction)| {
+                let player = Player {
+                    id,
+                    name: format!("player {id}"),
+                    direction,
+                    score: 0,
+                };
+                (id, player)
+            })
+            .collect::<HashMap<_, _>>();
+        GameState::new(players, ids[0], Variant::American)
+    }
+    fn dispatch(state: &mut GameState, event: GameEvent) {
+        state.dispatch(&event).unwrap();
+    }
+    /// plays the last legal move of each player in turn, as the server would
+    fn play(state: &mut GameState, plies: usize) {
+        for _ in 0..plies {
+            let player_id = state.is_turn;
+            let mv = legal_moves(&state.grid, player_id).pop().unwrap();
+            dispatch(state, GameEvent::Move { mv, player_id });
+            let opponent = state.players.keys().copied().find(|id| *id != player_id);
+            dispatch(
+                state,
+                GameEvent::TurnChanged {
+                    player_id: opponent.unwrap(),
+                },
+            );
+        }
+    }
+    /// the game replayed from its log, through its bytes as saved by the server
+    fn replayed(state: &GameState) -> GameState {
+        let bytes = EventLog::from_game(state).to_bytes().unwrap();
+        EventLog::from_bytes(&bytes).unwrap().replay().unwrap()
+    }
+    fn assert_same(replayed: &GameState, live: &GameState) {
+        assert_eq!(replayed.grid, live.grid);
+        assert_eq!(replayed.is_turn, live.is_turn);
+        assert_eq!(replayed.players, live.players);
+        assert_eq!(replayed.hash(), live.hash());
+        assert!(replayed.moves().eq(live.moves()));
+        assert_eq!(replayed.history().len(), live.history().len());
+        assert_eq!(replayed.draw_offer, live.draw_offer);
+        assert_eq!(replayed.takeback_request, live.takeback_request);
+    }
+
+    #[test]
+    fn replaying_a_log_gives_the_live_game_back() {
+        let [first, second] = [10, 20];
+        let mut state = new_game([first, second]);
+        play(&mut state, 6);
+        dispatch(&mut state, GameEvent::DrawOffered { player_id: first });
+        dispatch(
+            &mut state,
+            GameEvent::DrawAnswered {
+                player_id: second,
+                accepted: false,
+            },
+        );
+        assert_same(&replayed(&state), &state);
+
+        // takebacks, declined and accepted
+        dispatch(
+            &mut state,
+            GameEvent::TakebackRequested { player_id: first },
+        );
+        dispatch(
+            &mut state,
+            GameEvent::TakebackAnswered {
+                player_id: second,
+                accepted: false,
+            },
+        );
+        dispatch(
+            &mut state,
+            GameEvent::TakebackRequested { player_id: first },
+        );
+        dispatch(
+            &mut state,
+            GameEvent::TakebackAnswered {
+                player_id: second,
+                accepted: true,
+            },
+        );
+        assert_eq!(state.moves().count(), 4);
+        assert_same(&replayed(&state), &state);
+
+        play(&mut state, 5);
+        dispatch(
+            &mut state,
+            GameEvent::TakebackRequested { player_id: second },
+        );
+        assert_same(&replayed(&state), &state);
+        dispatch(
+            &mut state,
+            GameEvent::TakebackAnswered {
+                player_id: first,
+                accepted: true,
+            },
+        );
+        play(&mut state, 3);
+        dispatch(
+            &mut state,
+            GameEvent::EndGame {
+                reason: EndGameReason::PlayerLeft { player_id: second },
+            },
+        );
+        assert_same(&replayed(&state), &state);
+    }
+
+    #[test]
+    fn resumed_games_replay_with_the_new_seats() {
+        let mut state = new_game([10, 20]);
+        play(&mut state, 5);
+        dispatch(&mut state, GameEvent::TakebackRequested { player_id: 10 });
+        dispatch(
+            &mut state,
+            GameEvent::TakebackAnswered {
+                player_id: 20,
+                accepted: true,
+            },
+        );
+
+        // saved on shutdown, and resumed by players reconnecting with new ids
+        let bytes = postcard::to_allocvec(&state).unwrap();
+        let mut resumed = postcard::from_bytes::<GameState>(&bytes).unwrap();
+        resumed.reseat(10, 11);
+        resumed.reseat(20, 21);
+        assert!(resumed.players.contains_key(&11) && resumed.players.contains_key(&21));
+        assert_eq!(resumed.grid.players(), [11, 21]);
+
+        // what clients rebuild from the log of `GameResumed`
+        let mut client = replayed(&resumed);
+        assert_same(&client, &resumed);
+        assert!(
+            client
+                .history()
+                .iter()
+                .all(|event| event.sender() != Some(10))
+        );
+
+        // and the game goes on the same way on both sides
+        for state in [&mut resumed, &mut client] {
+            play(state, 4);
+            dispatch(state, GameEvent::TakebackRequested { player_id: 21 });
+            dispatch(
+                state,
+                GameEvent::TakebackAnswered {
+                    player_id: 11,
+                    accepted: true,
+                },
+            );
+        }
+        assert_same(&client, &resumed);
+        assert_same(&replayed(&resumed), &resumed);
+    }
+}
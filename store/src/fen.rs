@@ -0,0 +1,202 @@
+//! Positions as text, in the draughts FEN format: `W:W21,22,K23:B1,2,3` is white to move,
+//! with white pawns on 21 and 22, a white king on 23 and black pawns on 1, 2 and 3.
+//! White is the player at the bottom of the board, and squares are numbered as in
+//! `Board::square_number`.
+use std::collections::HashMap;
+
+use crate::{
+    board::Board,
+    game_state::GameState,
+    piece::{Piece, PieceType},
+    player::{Player, PlayerId},
+    rules::Variant,
+};
+
+impl Board {
+    /// parses a FEN position, returning the board and the player to move.
+    /// `players` are the white (bottom) and the black (top) player
+    pub fn from_fen(
+        fen: &str,
+        variant: Variant,
+        players: [PlayerId; 2],
+    ) -> Result<(Board, PlayerId), String> {
+        let fen = fen
+            .trim()
+            .trim_matches('"')
+            .trim_end_matches('.')
+            .replace(char::is_whitespace, "");
+        let mut fields = fen.split(':');
+        let turn = match fields.next() {
+            Some("W") => players[0],
+            Some("B") => players[1],
+            other => return Err(format!("Invalid side to move: {:?}", other)),
+        };
+
+        let mut board = Board::empty(variant, players);
+        for field in fields {
+            let (player_id, squares) = match field.split_at_checked(1) {
+                Some(("W", squares)) => (players[0], squares),
+                Some(("B", squares)) => (players[1], squares),
+                _ => return Err(format!("Invalid piece list: {field}")),
+            };
+            for square in squares.split(',').filter(|square| !square.is_empty()) {
+                let (piece_type, numbers) = match square.strip_prefix('K') {
+                    Some(numbers) => (PieceType::King, numbers),
+                    None => (PieceType::Pawn, square),
+                };
+                // ranges like `1-12` are allowed as well
+                let (first, last) = numbers.split_once('-').unwrap_or((numbers, numbers));
+                let parse = |number: &str| {
+                    number
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid square: {square}"))
+                };
+                for number in parse(first)?..=parse(last)? {
+                    let Some(coords) = board.square_coords(number) else {
+                        return Err(format!("Square {number} is not on the board"));
+                    };
+                    if board[coords].is_some() {
+                        return Err(format!("Square {number} is listed twice"));
+                    }
+                    board.set(coords, Some(Piece::new(piece_type, player_id)));
+                }
+            }
+        }
+        Ok((board, turn))
+    }
+
+    pub fn to_fen(&self, turn: PlayerId) -> String {
+        let [white, black] = self.players();
+        let pieces = |player_id: PlayerId| {
+            self.coords()
+                .filter_map(|coords| {
+                    let piece = self[coords].filter(|piece| piece.player_id == player_id)?;
+                    let number = self.square_number(coords)?;
+                    Some(if piece.is_king() {
+                        format!("K{number}")
+                    } else {
+                        number.to_string()
+                    })
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        format!(
+            "{}:W{}:B{}",
+            if turn == black { "B" } else { "W" },
+            pieces(white),
+            pieces(black)
+        )
+    }
+}
+
+impl GameState {
+    /// a game starting from a FEN position, where white is the player moving up the board
+    pub fn from_fen(
+        fen: &str,
+        players: HashMap<PlayerId, Player>,
+        variant: Variant,
+    ) -> Result<GameState, String> {
+        let white = players.values().find(|player| player.direction == 1);
+        let black = players.values().find(|player| player.direction == -1);
+        let (Some(white), Some(black)) = (white, black) else {
+            return Err("A game needs a player moving up and one moving down".to_string());
+        };
+        let (grid, turn) = Board::from_fen(fen, variant, [white.id, black.id])?;
+        Ok(GameState::from_position(players, grid, turn))
+    }
+
+    pub fn to_fen(&self) -> String {
+        self.grid.to_fen(self.is_turn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYERS: [PlayerId; 2] = [1, 2];
+
+    #[test]
+    fn starting_positions_round_trip() {
+        for variant in Variant::ALL {
+            let board = Board::starting_position(variant, PLAYERS);
+            for turn in PLAYERS {
+                let fen = board.to_fen(turn);
+                assert_eq!(
+                    Board::from_fen(&fen, variant, PLAYERS),
+                    Ok((board.clone(), turn))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn american_starting_position() {
+        let board = Board::starting_position(Variant::American, PLAYERS);
+        let fen = "W:W21,22,23,24,25,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,8,9,10,11,12";
+        assert_eq!(board.to_fen(PLAYERS[0]), fen);
+        // ranges, spaces, quotes and a final dot are accepted too
+        let (parsed, turn) =
+            Board::from_fen("\"W:W21-32:B 1-12.\"", Variant::American, PLAYERS).unwrap();
+        assert_eq!((parsed, turn), (board, PLAYERS[0]));
+    }
+
+    #[test]
+    fn kings_round_trip() {
+        let fen = "B:WK1,18,K32:B5,K14";
+        let (board, turn) = Board::from_fen(fen, Variant::American, PLAYERS).unwrap();
+        assert_eq!(turn, PLAYERS[1]);
+        let king = |number| board[board.square_coords(number).unwrap()].unwrap();
+        assert!(king(1).is_king() && king(32).is_king() && king(14).is_king());
+        assert!(!king(18).is_king() && !king(5).is_king());
+        assert_eq!(king(14).player_id, PLAYERS[1]);
+        assert_eq!(board.to_fen(turn), fen);
+
+        let fen = "W:WK46,50:BK1";
+        let (board, turn) = Board::from_fen(fen, Variant::International, PLAYERS).unwrap();
+        assert_eq!(board.to_fen(turn), fen);
+    }
+
+    #[test]
+    fn malformed_positions_are_rejected() {
+        for fen in [
+            "",
+            "X:W21:B1",
+            "W:W21:X1",
+            "W:W21:Bone",
+            "W:W21:B1-",
+            "W:W21:B33",
+            "W:W21:B0",
+            "W:W21:B21",
+            "W:W1-12:B12",
+        ] {
+            assert!(
+                Board::from_fen(fen, Variant::American, PLAYERS).is_err(),
+                "{fen}"
+            );
+        }
+        // a side without pieces is not
+        assert!(Board::from_fen("W:W21:B", Variant::American, PLAYERS).is_ok());
+        // a square of the 10x10 board isn't on the 8x8 one
+        assert!(Board::from_fen("W:W50", Variant::International, PLAYERS).is_ok());
+        assert!(Board::from_fen("W:W50", Variant::American, PLAYERS).is_err());
+    }
+
+    #[test]
+    fn games_need_a_player_on_each_side() {
+        let player = |id, direction| Player {
+            id,
+            name: id.to_string(),
+            direction,
+            score: 0,
+        };
+        let players = HashMap::from([(1, player(1, 1)), (2, player(2, -1))]);
+        let state = GameState::from_fen("B:W21:B1", players, Variant::American).unwrap();
+        assert_eq!(state.is_turn, 2);
+        assert_eq!(state.to_fen(), "B:W21:B1");
+
+        let players = HashMap::from([(1, player(1, 1)), (2, player(2, 1))]);
+        assert!(GameState::from_fen("W:W21:B1", players, Variant::American).is_err());
+    }
+}
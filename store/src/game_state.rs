@@ -1,64 +1,355 @@
 use std::collections::HashMap;
 
 use crate::{
-    board::Board,
-    game_utils::Move,
-    piece::{Piece, PieceType},
+    DRAW_MOVE_LIMIT,
+    board::{Board, MoveRecord},
+    event_log::EventLog,
+    game_utils::{Move, legal_moves},
     player::{Player, PlayerId},
+    rules::Variant,
+    zobrist::position_hash,
 };
 use cli_log::info;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug)]
 pub enum ClientEvent {
-    GoToGame(HashMap<PlayerId, Player>, PlayerId),
+    GoToGame(HashMap<PlayerId, Player>, PlayerId, Variant),
+    ResumeGame(Box<EventLog>),
     GoToMenu(EndGameReason),
     GoToLobby(String, String),
     SendToServer(GameEvent),
+    // reviewing a finished game with an engine, then going back to the menu
+    AnalyseGame(Box<GameState>),
+    LeaveAnalysis,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum EndGameReason {
     PlayerLeft { player_id: PlayerId },
     PlayerWon { winner: PlayerId },
+    Draw { kind: DrawKind },
+}
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DrawKind {
+    /// too many moves in a row without captures or pawn moves
+    MoveLimit,
+    /// the same position occurred three times with the same player to move
+    Repetition,
+    /// a player offered a draw and the opponent accepted it
+    Agreement,
+    /// the endgame tablebases show that neither player can win any more
+    Adjudication,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GameEvent {
-    PlayerJoined { player: Player },
-    PlayerLeft { player_id: PlayerId },
-    EndGame { reason: EndGameReason },
-    TurnChanged { player_id: PlayerId },
-    Move { mv: Move, player_id: PlayerId },
+    PlayerJoined {
+        player: Player,
+    },
+    PlayerLeft {
+        player_id: PlayerId,
+    },
+    EndGame {
+        reason: EndGameReason,
+    },
+    TurnChanged {
+        player_id: PlayerId,
+    },
+    Move {
+        mv: Move,
+        player_id: PlayerId,
+    },
+    VariantAnnounced {
+        variant: Variant,
+    },
+    DrawOffered {
+        player_id: PlayerId,
+    },
+    DrawAnswered {
+        player_id: PlayerId,
+        accepted: bool,
+    },
+    TakebackRequested {
+        player_id: PlayerId,
+    },
+    /// an accepted takeback undoes moves until it is the requesting player's turn again
+    TakebackAnswered {
+        player_id: PlayerId,
+        accepted: bool,
+    },
+    /// a saved game picked up where it was left, rebuilt from its log
+    GameResumed {
+        log: Box<EventLog>,
+    },
 }
+impl GameEvent {
+    /// the player a client sends this event for, `None` for the events only the server creates
+    pub fn sender(&self) -> Option<PlayerId> {
+        match self {
+            GameEvent::Move { player_id, .. }
+            | GameEvent::DrawOffered { player_id }
+            | GameEvent::DrawAnswered { player_id, .. }
+            | GameEvent::TakebackRequested { player_id }
+            | GameEvent::TakebackAnswered { player_id, .. } => Some(*player_id),
+            GameEvent::PlayerJoined { .. }
+            | GameEvent::PlayerLeft { .. }
+            | GameEvent::EndGame { .. }
+            | GameEvent::TurnChanged { .. }
+            | GameEvent::VariantAnnounced { .. }
+            | GameEvent::GameResumed { .. } => None,
+        }
+    }
 
-#[derive(Debug)]
+    /// replaces the id of player `from` with `to`
+    fn reseat(&mut self, from: PlayerId, to: PlayerId) {
+        let reseat = |player_id: &mut PlayerId| {
+            if *player_id == from {
+                *player_id = to;
+            }
+        };
+        match self {
+            GameEvent::PlayerJoined { player } => reseat(&mut player.id),
+            GameEvent::PlayerLeft { player_id }
+            | GameEvent::TurnChanged { player_id }
+            | GameEvent::Move { player_id, .. }
+            | GameEvent::DrawOffered { player_id }
+            | GameEvent::DrawAnswered { player_id, .. }
+            | GameEvent::TakebackRequested { player_id }
+            | GameEvent::TakebackAnswered { player_id, .. } => reseat(player_id),
+            GameEvent::EndGame { reason } => match reason {
+                EndGameReason::PlayerLeft { player_id } => reseat(player_id),
+                EndGameReason::PlayerWon { winner } => reseat(winner),
+                EndGameReason::Draw { .. } => {}
+            },
+            GameEvent::VariantAnnounced { .. } | GameEvent::GameResumed { .. } => {}
+        }
+    }
+}
+
+/// a move as it was reduced, with what is needed to undo it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Ply {
+    record: MoveRecord,
+    player_id: PlayerId,
+    quiet_moves: usize,
+    // the positions cleared by the move, if it was a capture or a pawn move
+    positions: Vec<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameState {
     pub grid: Board,
     pub is_turn: PlayerId,
     pub players: HashMap<PlayerId, Player>,
     history: Vec<GameEvent>,
+    // the position the game started from, with the player to move, and who was playing
+    initial_position: (Board, PlayerId),
+    initial_players: HashMap<PlayerId, Player>,
+    /// moves by each player in a row without a capture or a pawn move after which the game is
+    /// drawn, see `DRAW_MOVE_LIMIT`
+    pub draw_move_limit: usize,
+    // plies in a row without a capture or a pawn move, two for each move of the limit
+    quiet_moves: usize,
+    // hashes of the positions since the last capture or pawn move, with the player to move
+    positions: Vec<u64>,
+    pub draw_offer: Option<PlayerId>,
+    draw_agreed: bool,
+    pub takeback_request: Option<PlayerId>,
+    // moves that can be undone, and moves undone that can be played again
+    plies: Vec<Ply>,
+    undone: Vec<(Move, PlayerId)>,
 }
 impl GameState {
-    pub fn new(players: HashMap<PlayerId, Player>, starting_turn: PlayerId) -> Self {
+    pub fn new(
+        players: HashMap<PlayerId, Player>,
+        starting_turn: PlayerId,
+        variant: Variant,
+    ) -> Self {
+        let grid = Board::new(&players, starting_turn, variant);
+        Self::from_position(players, grid, starting_turn)
+    }
+    /// a game starting from any position, with `is_turn` to move
+    pub fn from_position(
+        players: HashMap<PlayerId, Player>,
+        grid: Board,
+        is_turn: PlayerId,
+    ) -> Self {
         Self {
-            grid: Board::new(&players, starting_turn),
-            is_turn: starting_turn,
-            players: players,
+            positions: vec![position_hash(&grid, is_turn)],
+            initial_position: (grid.clone(), is_turn),
+            initial_players: players.clone(),
+            grid,
+            is_turn,
+            players,
             history: vec![],
+            draw_move_limit: DRAW_MOVE_LIMIT,
+            quiet_moves: 0,
+            draw_offer: None,
+            draw_agreed: false,
+            takeback_request: None,
+            plies: vec![],
+            undone: vec![],
+        }
+    }
+    /// every event reduced so far, in order: replaying them gives the current state back, as
+    /// long as no move was undone or redone locally, these not being events
+    pub fn history(&self) -> &[GameEvent] {
+        &self.history
+    }
+    pub fn initial_position(&self) -> (&Board, PlayerId) {
+        (&self.initial_position.0, self.initial_position.1)
+    }
+    pub fn initial_players(&self) -> &HashMap<PlayerId, Player> {
+        &self.initial_players
+    }
+    /// the moves that led to the current position, taken back ones excluded
+    pub fn moves(&self) -> impl Iterator<Item = &Move> {
+        self.plies.iter().map(|ply| &ply.record.mv)
+    }
+    /// rebuilds a game by dispatching the recorded events, in order, to its initial state
+    pub fn replay(initial: GameState, events: &[GameEvent]) -> Result<GameState, String> {
+        let mut state = initial;
+        for (i, event) in events.iter().enumerate() {
+            state
+                .dispatch(event)
+                .map_err(|err| format!("Event {i} ({event:?}) cannot be replayed: {err}"))?;
         }
+        Ok(state)
+    }
+    /// takes back the last move, giving the turn back to the player who made it; in a game
+    /// played against someone, moves are taken back with a takeback request instead
+    pub fn undo(&mut self) -> Result<(), String> {
+        let Some(ply) = self.plies.pop() else {
+            return Err("There is no move to undo".to_string());
+        };
+        self.grid.undo(&ply.record)?;
+        if let Some(player) = self.players.get_mut(&ply.player_id) {
+            player.score = player.score.saturating_sub(ply.record.captured.len());
+        }
+        self.quiet_moves = ply.quiet_moves;
+        self.positions.pop();
+        self.positions.extend(ply.positions);
+        self.is_turn = ply.player_id;
+        self.undone.push((ply.record.mv, ply.player_id));
+        Ok(())
+    }
+    /// plays again the last move undone, passing the turn to the opponent
+    pub fn redo(&mut self) -> Result<(), String> {
+        let Some((mv, player_id)) = self.undone.pop() else {
+            return Err("There is no move to redo".to_string());
+        };
+        self.validate(&GameEvent::Move {
+            mv: mv.clone(),
+            player_id,
+        })?;
+        self.move_pawn(&mv, player_id)?;
+        self.next_turn();
+        Ok(())
+    }
+    pub fn can_undo(&self) -> bool {
+        !self.plies.is_empty()
+    }
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
     }
+    /// undoes moves until it is `player_id`'s turn again, taking back at least one of their moves
+    fn take_back(&mut self, player_id: PlayerId) -> Result<(), String> {
+        loop {
+            let mover = self.plies.last().map(|ply| ply.player_id);
+            self.undo()?;
+            if mover == Some(player_id) {
+                // both players agreed to take the moves back, they are not to be redone
+                self.undone.clear();
+                return Ok(());
+            }
+        }
+    }
+
+    /// hands the seat of player `from` to player `to`, e.g. when a player reconnects to a
+    /// resumed game with a new id
+    pub fn reseat(&mut self, from: PlayerId, to: PlayerId) {
+        let reseat = |player_id: &mut PlayerId| {
+            if *player_id == from {
+                *player_id = to;
+            }
+        };
+        for players in [&mut self.players, &mut self.initial_players] {
+            if let Some(mut player) = players.remove(&from) {
+                player.id = to;
+                players.insert(to, player);
+            }
+        }
+        reseat(&mut self.is_turn);
+        reseat(&mut self.initial_position.1);
+        if let Some(player_id) = self.draw_offer.as_mut() {
+            reseat(player_id);
+        }
+        if let Some(player_id) = self.takeback_request.as_mut() {
+            reseat(player_id);
+        }
+        self.grid.reseat(from, to);
+        self.initial_position.0.reseat(from, to);
+        for ply in self.plies.iter_mut() {
+            reseat(&mut ply.player_id);
+            for (_, piece) in ply.record.captured.iter_mut() {
+                reseat(&mut piece.player_id);
+            }
+        }
+        for (_, player_id) in self.undone.iter_mut() {
+            reseat(player_id);
+        }
+        for event in self.history.iter_mut() {
+            event.reseat(from, to);
+        }
+    }
+
     pub fn next_turn(&mut self) -> PlayerId {
         // TODO: mi piace usare * ?
-        let next_player = self
-            .players
-            .keys()
-            .filter(|id| **id != self.is_turn)
-            .next()
-            .unwrap();
+        let next_player = self.players.keys().find(|id| **id != self.is_turn).unwrap();
         self.is_turn = *next_player;
         *next_player
     }
 
+    /// checks whether the last reduced event ended the game: the player who just moved wins
+    /// when the opponent has no pieces left or none of them can move, otherwise the game
+    /// can end in a draw by agreement, move limit or threefold repetition
+    pub fn check_end_game(&self) -> Option<GameEvent> {
+        let opponent = self.players.values().find(|p| p.id != self.is_turn)?;
+        let reason = if legal_moves(&self.grid, opponent.id).is_empty() {
+            EndGameReason::PlayerWon {
+                winner: self.is_turn,
+            }
+        } else if self.draw_agreed {
+            EndGameReason::Draw {
+                kind: DrawKind::Agreement,
+            }
+        } else if self.quiet_moves >= 2 * self.draw_move_limit {
+            EndGameReason::Draw {
+                kind: DrawKind::MoveLimit,
+            }
+        } else if self.repetitions() >= 3 {
+            EndGameReason::Draw {
+                kind: DrawKind::Repetition,
+            }
+        } else {
+            return None;
+        };
+        Some(GameEvent::EndGame { reason })
+    }
+
+    /// the zobrist hash of the current position, with the player to move
+    pub fn hash(&self) -> u64 {
+        position_hash(&self.grid, self.is_turn)
+    }
+
+    /// how many times the current position occurred, with the same player to move
+    fn repetitions(&self) -> usize {
+        match self.positions.last() {
+            Some(current) => self.positions.iter().filter(|p| *p == current).count(),
+            None => 0,
+        }
+    }
+
     pub fn dispatch(&mut self, event: &GameEvent) -> Result<(), String> {
         self.validate(event)?;
         self.reduce(event)?;
@@ -68,23 +359,45 @@ impl GameState {
         self.history.push(event.clone());
         match event {
             GameEvent::Move { mv, player_id } => {
+                // a new move makes the undone ones impossible to redo
+                self.undone.clear();
                 self.move_pawn(mv, *player_id)?;
-                return Ok(None);
+                Ok(None)
             }
             GameEvent::TurnChanged { player_id } => {
                 self.is_turn = *player_id;
-                return Ok(None);
+                Ok(None)
+            }
+            GameEvent::PlayerJoined { .. } => Ok(None),
+            GameEvent::VariantAnnounced { .. } => Ok(None),
+            GameEvent::GameResumed { .. } => Ok(None),
+            GameEvent::DrawOffered { player_id } => {
+                self.draw_offer = Some(*player_id);
+                Ok(None)
             }
-            GameEvent::PlayerJoined { .. } => {
-                return Ok(None);
+            GameEvent::DrawAnswered { accepted, .. } => {
+                self.draw_offer = None;
+                self.draw_agreed = *accepted;
+                Ok(None)
+            }
+            GameEvent::TakebackRequested { player_id } => {
+                self.takeback_request = Some(*player_id);
+                Ok(None)
+            }
+            GameEvent::TakebackAnswered { accepted, .. } => {
+                let requesting = self.takeback_request.take();
+                if *accepted && let Some(player_id) = requesting {
+                    self.take_back(player_id)?;
+                }
+                Ok(None)
             }
             GameEvent::PlayerLeft { player_id } => {
                 self.players.remove(player_id).unwrap();
-                return Ok(None);
+                Ok(None)
             }
             GameEvent::EndGame { reason } => {
                 info!("Game ended: {:?}", reason);
-                return Ok(Some(ClientEvent::GoToMenu(reason.clone())));
+                Ok(Some(ClientEvent::GoToMenu(reason.clone())))
             }
         }
     }
@@ -99,13 +412,31 @@ impl GameState {
                     ));
                 }
             }
-            GameEvent::Move { player_id, .. } => {
+            GameEvent::Move { mv, player_id } => {
                 if self.is_turn != *player_id {
                     return Err(format!("Not your turn, player {}", player_id));
                 }
+                // don't trust the coordinates before indexing the board with them
+                let path = mv.path();
+                if path.len() < 2 || path.iter().any(|c| !self.grid.contains(*c)) {
+                    return Err(format!(
+                        "Malformed move from player {}: {:?}",
+                        player_id, mv
+                    ));
+                }
+                if self.grid[mv.from()].is_none_or(|piece| piece.player_id != *player_id) {
+                    return Err(format!(
+                        "Player {} has no piece in {:?}",
+                        player_id,
+                        mv.from()
+                    ));
+                }
+                if !legal_moves(&self.grid, *player_id).contains(mv) {
+                    return Err(format!("Illegal move from player {}: {:?}", player_id, mv));
+                }
             }
             GameEvent::PlayerLeft { player_id } => {
-                if !self.players.contains_key(&player_id) {
+                if !self.players.contains_key(player_id) {
                     return Err(format!("Player is not playing: {}", player_id));
                 }
             }
@@ -114,31 +445,370 @@ impl GameState {
                     return Err(format!("Player {player_id} is already playing"));
                 }
             }
+            GameEvent::DrawOffered { player_id } => {
+                if !self.players.contains_key(player_id) {
+                    return Err(format!("Player is not playing: {}", player_id));
+                }
+                if self.draw_offer.is_some() {
+                    return Err("A draw was already offered".to_string());
+                }
+            }
+            GameEvent::DrawAnswered { player_id, .. } => {
+                // only the opponent of the player who offered it can answer
+                if !self.players.contains_key(player_id)
+                    || self
+                        .draw_offer
+                        .is_none_or(|offering| offering == *player_id)
+                {
+                    return Err(format!("No draw was offered to player {player_id}"));
+                }
+            }
+            GameEvent::TakebackRequested { player_id } => {
+                if !self.players.contains_key(player_id) {
+                    return Err(format!("Player is not playing: {}", player_id));
+                }
+                if self.takeback_request.is_some() {
+                    return Err("A takeback was already requested".to_string());
+                }
+                if !self.plies.iter().any(|ply| ply.player_id == *player_id) {
+                    return Err(format!("Player {player_id} has no move to take back"));
+                }
+            }
+            GameEvent::TakebackAnswered { player_id, .. } => {
+                // only the opponent of the player who requested it can answer
+                if !self.players.contains_key(player_id)
+                    || self
+                        .takeback_request
+                        .is_none_or(|requesting| requesting == *player_id)
+                {
+                    return Err(format!("No takeback was requested to player {player_id}"));
+                }
+            }
+            GameEvent::VariantAnnounced { variant } => {
+                if *variant != self.grid.variant() {
+                    return Err(format!(
+                        "The game is already played as {}",
+                        self.grid.variant()
+                    ));
+                }
+            }
+            GameEvent::GameResumed { .. } => {
+                return Err("A game can't be resumed in the middle of another one".to_string());
+            }
             GameEvent::EndGame { .. } => {}
         }
         Ok(())
     }
 
-    // TODO: catch errors
     fn move_pawn(&mut self, mv: &Move, player_id: PlayerId) -> Result<(), String> {
-        self.grid[mv.to()] = Some(Piece {
-            piece_type: PieceType::Pawn,
+        let Some(piece) = self.grid[mv.from()] else {
+            return Err(format!("No piece to move in {:?}", mv.from()));
+        };
+
+        let quiet_moves = self.quiet_moves;
+        let mut cleared = vec![];
+        // only king moves without captures count towards the move limit, and any other
+        // move makes the previous positions impossible to repeat
+        if piece.is_king() && !mv.is_capture() {
+            self.quiet_moves += 1;
+        } else {
+            self.quiet_moves = 0;
+            cleared = std::mem::take(&mut self.positions);
+        }
+
+        let record = self.grid.play(mv)?;
+        if let Some(player) = self.players.get_mut(&player_id) {
+            player.score += record.captured.len();
+        }
+        self.plies.push(Ply {
+            record,
             player_id,
+            quiet_moves,
+            positions: cleared,
         });
 
-        // remove selected pawn from prev cell
-        self.grid[mv.from()] = None;
+        let next_player = self
+            .players
+            .keys()
+            .find(|id| **id != player_id)
+            .copied()
+            .unwrap_or(player_id);
+        self.positions.push(position_hash(&self.grid, next_player));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        // eat if thats the case
-        match mv {
-            Move::Simple { .. } => {}
-            Move::Capture { eat, .. } => {
-                self.grid[*eat] = None;
-                if let Some(player) = self.players.get_mut(&self.is_turn) {
-                    player.score += 1;
-                }
-            }
+    const WHITE: PlayerId = 1;
+    const BLACK: PlayerId = 2;
+
+    /// a game of american checkers from a FEN position, white moving up the board
+    fn game(fen: &str) -> GameState {
+        let players = [(WHITE, "white", 1), (BLACK, "black", -1)]
+            .into_iter()
+            .map(|(id, name, direction)| {
+                let player = Player {
+                    id,
+                    name: name.to_string(),
+                    direction,
+                    score: 0,
+                };
+                (id, player)
+            })
+            .collect();
+        GameState::from_fen(fen, players, Variant::American).unwrap()
+    }
+
+    /// plays the move between two squares as the server would, passing the turn unless it
+    /// ended the game
+    fn play(state: &mut GameState, from: usize, to: usize) -> Option<GameEvent> {
+        let mv = legal_moves(&state.grid, state.is_turn)
+            .into_iter()
+            .find(|mv| {
+                state.grid.square_number(mv.from()) == Some(from)
+                    && state.grid.square_number(mv.to()) == Some(to)
+            })
+            .unwrap();
+        let player_id = state.is_turn;
+        state.dispatch(&GameEvent::Move { mv, player_id }).unwrap();
+        if let Some(end_game) = state.check_end_game() {
+            return Some(end_game);
         }
-        Ok(())
+        let player_id = if player_id == WHITE { BLACK } else { WHITE };
+        state
+            .dispatch(&GameEvent::TurnChanged { player_id })
+            .unwrap();
+        None
+    }
+    fn is_draw(end_game: Option<GameEvent>, draw: DrawKind) -> bool {
+        matches!(end_game, Some(GameEvent::EndGame { reason: EndGameReason::Draw { kind } }) if kind == draw)
+    }
+
+    #[test]
+    fn a_capture_chain_scores_every_piece() {
+        let mut state = game("W:W30:B26,19,10");
+        let moves = legal_moves(&state.grid, WHITE);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].eaten().len(), 2);
+        state
+            .dispatch(&GameEvent::Move {
+                mv: moves[0].clone(),
+                player_id: WHITE,
+            })
+            .unwrap();
+        assert_eq!(state.players[&WHITE].score, 2);
+        assert_eq!(state.to_fen(), "W:W16:B10");
+    }
+
+    #[test]
+    fn the_move_limit_counts_moves_of_both_players() {
+        let mut state = game("W:WK29:BK4");
+        state.draw_move_limit = 2;
+        assert!(play(&mut state, 29, 25).is_none());
+        assert!(play(&mut state, 4, 8).is_none());
+        assert!(play(&mut state, 25, 22).is_none());
+        assert!(is_draw(play(&mut state, 8, 11), DrawKind::MoveLimit));
+    }
+
+    #[test]
+    fn pawn_moves_reset_the_move_limit() {
+        let mut state = game("W:WK29,21:BK4");
+        state.draw_move_limit = 2;
+        assert!(play(&mut state, 29, 25).is_none());
+        assert!(play(&mut state, 4, 8).is_none());
+        assert!(play(&mut state, 21, 17).is_none());
+        assert!(play(&mut state, 8, 11).is_none());
+    }
+
+    #[test]
+    fn positions_repeated_three_times_are_drawn() {
+        let mut state = game("W:WK29:BK4");
+        let shuffle = [(29, 25), (4, 8), (25, 29), (8, 4)];
+        for (from, to) in shuffle.iter().chain(&shuffle[..3]) {
+            assert!(play(&mut state, *from, *to).is_none());
+        }
+        // back to the starting position, for the third time
+        assert!(is_draw(play(&mut state, 8, 4), DrawKind::Repetition));
+    }
+
+    #[test]
+    fn only_the_opponent_answers_a_draw_offer() {
+        let mut state = game("W:WK29:BK4");
+        let answer = |player_id| GameEvent::DrawAnswered {
+            player_id,
+            accepted: true,
+        };
+        assert!(state.dispatch(&answer(BLACK)).is_err());
+        state
+            .dispatch(&GameEvent::DrawOffered { player_id: WHITE })
+            .unwrap();
+        assert!(state.dispatch(&answer(WHITE)).is_err());
+        assert!(state.dispatch(&answer(3)).is_err());
+        state.dispatch(&answer(BLACK)).unwrap();
+        assert!(is_draw(state.check_end_game(), DrawKind::Agreement));
+    }
+
+    #[test]
+    fn clients_only_send_events_for_themselves() {
+        let events = [
+            GameEvent::DrawOffered { player_id: WHITE },
+            GameEvent::TakebackAnswered {
+                player_id: BLACK,
+                accepted: true,
+            },
+        ];
+        assert_eq!(
+            events.map(|event| event.sender()),
+            [Some(WHITE), Some(BLACK)]
+        );
+        let server_events = [
+            GameEvent::TurnChanged { player_id: WHITE },
+            GameEvent::PlayerLeft { player_id: WHITE },
+            GameEvent::EndGame {
+                reason: EndGameReason::PlayerWon { winner: WHITE },
+            },
+        ];
+        assert!(server_events.iter().all(|event| event.sender().is_none()));
+    }
+
+    #[test]
+    fn a_takeback_gives_the_turn_back_to_the_requesting_player() {
+        let mut state = game("W:W30,21:B26,1");
+        let initial = state.grid.clone();
+        assert!(
+            state
+                .dispatch(&GameEvent::TakebackRequested { player_id: WHITE })
+                .is_err()
+        );
+        assert!(play(&mut state, 30, 23).is_none());
+        assert!(play(&mut state, 1, 5).is_none());
+        assert_eq!(state.players[&WHITE].score, 1);
+
+        // white takes back their capture, and black's answer to it
+        state
+            .dispatch(&GameEvent::TakebackRequested { player_id: WHITE })
+            .unwrap();
+        state
+            .dispatch(&GameEvent::TakebackAnswered {
+                player_id: BLACK,
+                accepted: true,
+            })
+            .unwrap();
+        assert_eq!(state.grid, initial);
+        assert_eq!(state.is_turn, WHITE);
+        assert_eq!(state.players[&WHITE].score, 0);
+        assert_eq!(state.moves().count(), 0);
+        assert!(!state.can_undo());
+
+        // the same capture can be played again
+        assert!(play(&mut state, 30, 23).is_none());
+        assert_eq!(state.to_fen(), "B:W21,23:B1");
+    }
+
+    #[test]
+    fn a_takeback_undoes_only_the_requesting_players_last_move() {
+        let mut state = game("W:W30,21:B26,1");
+        assert!(play(&mut state, 30, 23).is_none());
+        let after_capture = state.to_fen();
+        assert!(play(&mut state, 1, 5).is_none());
+
+        // black takes back their own move only
+        state
+            .dispatch(&GameEvent::TakebackRequested { player_id: BLACK })
+            .unwrap();
+        state
+            .dispatch(&GameEvent::TakebackAnswered {
+                player_id: WHITE,
+                accepted: true,
+            })
+            .unwrap();
+        assert_eq!(state.to_fen(), after_capture);
+        assert_eq!(state.players[&WHITE].score, 1);
+    }
+
+    #[test]
+    fn only_the_opponent_answers_a_takeback_request() {
+        let mut state = game("W:W30,21:B26,1");
+        assert!(play(&mut state, 30, 23).is_none());
+        let answer = |player_id, accepted| GameEvent::TakebackAnswered {
+            player_id,
+            accepted,
+        };
+        assert!(state.dispatch(&answer(BLACK, true)).is_err());
+        state
+            .dispatch(&GameEvent::TakebackRequested { player_id: WHITE })
+            .unwrap();
+        assert!(
+            state
+                .dispatch(&GameEvent::TakebackRequested { player_id: BLACK })
+                .is_err()
+        );
+        assert!(state.dispatch(&answer(WHITE, true)).is_err());
+        assert!(state.dispatch(&answer(3, true)).is_err());
+
+        // a declined takeback changes nothing
+        let before = state.to_fen();
+        state.dispatch(&answer(BLACK, false)).unwrap();
+        assert_eq!(state.to_fen(), before);
+        assert_eq!(state.takeback_request, None);
+        assert!(state.dispatch(&answer(BLACK, true)).is_err());
+    }
+
+    #[test]
+    fn a_capture_with_a_promotion_is_undone_and_redone() {
+        let mut state = game("W:W11:B6,7,28");
+        let before = state.clone();
+        assert!(play(&mut state, 11, 2).is_none());
+        let after = state.to_fen();
+        assert_eq!(after, "B:WK2:B6,28");
+        assert!(!state.can_redo());
+
+        state.undo().unwrap();
+        assert_eq!(state.grid, before.grid);
+        assert_eq!(state.to_fen(), before.to_fen());
+        assert_eq!(state.is_turn, WHITE);
+        assert_eq!(state.players[&WHITE].score, 0);
+        assert_eq!(state.hash(), before.hash());
+        assert!(!state.can_undo() && state.can_redo());
+        assert!(state.undo().is_err());
+
+        state.redo().unwrap();
+        assert_eq!(state.to_fen(), after);
+        let crowned = state
+            .grid
+            .coords()
+            .find(|c| state.grid.square_number(*c) == Some(2));
+        assert!(state.grid[crowned.unwrap()].unwrap().is_king());
+        assert_eq!(state.players[&WHITE].score, 1);
+        assert!(state.can_undo() && !state.can_redo());
+        assert!(state.redo().is_err());
+    }
+
+    #[test]
+    fn a_new_move_clears_the_moves_to_redo() {
+        let mut state = game("W:W11,30:B6,7,28");
+        assert!(play(&mut state, 11, 2).is_none());
+        state.undo().unwrap();
+        assert!(state.can_redo());
+        assert!(play(&mut state, 11, 2).is_none());
+        assert!(!state.can_redo());
+
+        // nor can moves taken back by both players be redone
+        assert!(play(&mut state, 28, 32).is_none());
+        state.undo().unwrap();
+        state
+            .dispatch(&GameEvent::TakebackRequested { player_id: WHITE })
+            .unwrap();
+        state
+            .dispatch(&GameEvent::TakebackAnswered {
+                player_id: BLACK,
+                accepted: true,
+            })
+            .unwrap();
+        assert!(!state.can_redo());
+        assert_eq!(state.to_fen(), "W:W11,30:B6,7,28");
     }
 }
@@ -1,145 +1,424 @@
 use serde::{Deserialize, Serialize};
 
-use crate::{CELL_N, board::Board, coords::Coords, player::Player};
+use crate::{
+    bitboard::BitBoard,
+    board::{Board, Position},
+    coords::Coords,
+    piece::{Piece, PieceType},
+    player::PlayerId,
+    rules::{Promotion, Variant},
+};
 
-#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Move {
     Simple {
         from: Coords,
         to: Coords,
     },
+    /// a whole capture sequence: `path` holds every square the piece stands on, starting
+    /// from its original cell, and `eat` every square captured along the way, in order
     Capture {
-        from: Coords,
-        to: Coords,
-        eat: Coords,
+        path: Vec<Coords>,
+        eat: Vec<Coords>,
     },
 }
 impl Move {
     pub fn to(&self) -> Coords {
         match self {
-            Move::Capture { to, .. } => *to,
+            Move::Capture { path, .. } => *path.last().unwrap(),
             Move::Simple { to, .. } => *to,
         }
     }
     pub fn from(&self) -> Coords {
         match self {
-            Move::Capture { from, .. } => *from,
+            Move::Capture { path, .. } => path[0],
             Move::Simple { from, .. } => *from,
         }
     }
+    /// the same move with each cell replaced by `f(cell)`, e.g. on a rotated board
+    pub fn map_coords(&self, f: impl Fn(Coords) -> Coords) -> Move {
+        match self {
+            Move::Capture { path, eat } => Move::Capture {
+                path: path.iter().copied().map(&f).collect(),
+                eat: eat.iter().copied().map(&f).collect(),
+            },
+            Move::Simple { from, to } => Move::Simple {
+                from: f(*from),
+                to: f(*to),
+            },
+        }
+    }
+    /// every square the piece stands on during the move, origin included
+    pub fn path(&self) -> Vec<Coords> {
+        match self {
+            Move::Capture { path, .. } => path.clone(),
+            Move::Simple { from, to } => vec![*from, *to],
+        }
+    }
+    /// cells of the pieces captured by the move, in order
+    pub fn eaten(&self) -> &[Coords] {
+        match self {
+            Move::Capture { eat, .. } => eat,
+            Move::Simple { .. } => &[],
+        }
+    }
+    pub fn is_capture(&self) -> bool {
+        matches!(self, Move::Capture { .. })
+    }
 }
 
-fn _index_to_coords(i: usize) -> (usize, usize) {
-    (i / CELL_N, i % CELL_N)
+fn _index_to_coords(i: usize, width: usize) -> (usize, usize) {
+    (i / width, i % width)
 }
-pub fn coords_to_index(coords: Coords) -> usize {
-    coords.y * CELL_N + coords.x
+/// index of the cell when the board is read row by row
+pub fn coords_to_index(coords: Coords, width: usize) -> usize {
+    coords.y * width + coords.x
 }
-pub fn is_white(coords: Coords) -> bool {
-    (coords.x + coords.y) % 2 == 0
+/// the four diagonal directions as (dx, dy); "up" on the board is y - 1
+pub static DIAGONALS: [(i32, i32); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+
+/// the row a player's pawns get crowned on, i.e. the opponent's back row
+pub fn promotion_row(grid: &Board, player_id: PlayerId) -> usize {
+    if grid.direction(player_id) == 1 {
+        0
+    } else {
+        grid.height - 1
+    }
 }
 
-pub fn get_possible_moves(
-    grid: &Board,
-    original_cell: Coords,
-    player: &Player,
-) -> Result<Vec<Move>, String> {
+/// whether the piece making this move gets crowned by it
+pub fn promotes(grid: &Board, mv: &Move) -> bool {
+    let Some(piece) = grid[mv.from()] else {
+        return false;
+    };
+    if piece.is_king() {
+        return false;
+    }
+    let row = promotion_row(grid, piece.player_id);
+    match grid.rules().promotion() {
+        Promotion::Immediate => mv.path().iter().skip(1).any(|cell| cell.y == row),
+        Promotion::EndsMove | Promotion::AtEndOfMove => mv.to().y == row,
+    }
+}
+
+/// directions a piece can move in: pawns only go forward, kings go everywhere
+fn move_directions(grid: &Board, piece: &Piece) -> Vec<(i32, i32)> {
+    if piece.is_king() {
+        DIAGONALS.to_vec()
+    } else {
+        // direction 1 means moving up the board, so towards lower y
+        let direction = grid.direction(piece.player_id);
+        DIAGONALS
+            .into_iter()
+            .filter(|(_, dy)| *dy == -direction)
+            .collect()
+    }
+}
+
+/// directions a piece can capture in, which for pawns depends on the variant
+fn capture_directions(grid: &Board, piece: &Piece) -> Vec<(i32, i32)> {
+    if grid.rules().pawns_capture_backwards() {
+        DIAGONALS.to_vec()
+    } else {
+        move_directions(grid, piece)
+    }
+}
+
+/// every move a player can make, looking at all of their pieces: if any of them can
+/// capture, only the captures allowed by the variant are
+pub fn legal_moves(grid: &Board, player_id: PlayerId) -> Vec<Move> {
+    // american boards generate their moves from bitmasks, without allocating anything else
+    if grid.variant() == Variant::American
+        && let Ok(bitboard) = BitBoard::try_from(grid)
+    {
+        return bitboard.legal_moves(player_id);
+    }
+    legal_moves_by_cell(grid, player_id)
+}
+/// `legal_moves` looking at the cells one by one, for every variant
+pub fn legal_moves_by_cell(grid: &Board, player_id: PlayerId) -> Vec<Move> {
+    let rules = grid.rules();
+    let moves = grid
+        .coords()
+        .filter(|coords| grid[*coords].is_some_and(|p| p.player_id == player_id))
+        .filter_map(|coords| get_possible_moves(grid, coords).ok())
+        .flatten()
+        .collect::<Vec<_>>();
+
+    // rule: forced to capture if any piece can capture
+    if rules.mandatory_capture() && moves.iter().any(|mv| mv.is_capture()) {
+        let captures = moves.into_iter().filter(|mv| mv.is_capture()).collect();
+        rules.filter_captures(grid, captures)
+    } else {
+        moves
+    }
+}
+
+/// the moves of the piece in `original_cell` alone; see `legal_moves` for the whole board
+pub fn get_possible_moves(grid: &Board, original_cell: Coords) -> Result<Vec<Move>, String> {
+    let Some(piece) = grid[original_cell] else {
+        return Err(format!("No piece to move in {:?}", original_cell));
+    };
+    let rules = grid.rules();
     let mut moves = vec![];
 
     // check edible moves
-    original_cell
-        .diag()
-        .into_iter()
-        .filter(|cell| grid[*cell].is_some_and(|c| c.player_id != player.id))
-        .for_each(|edible_coords| {
-            let landing_coords = if player.direction == 1 {
-                // look towards up (+1)
-                if edible_coords.x > original_cell.x {
-                    if edible_coords.x < CELL_N - 1 && edible_coords.y > 0 {
-                        // this diagonal original_cell is on the right
-                        Some(Coords {
-                            x: edible_coords.x + 1,
-                            y: edible_coords.y - 1,
-                        })
-                    } else {
-                        None
-                    }
-                } else if edible_coords.x < original_cell.x {
-                    if edible_coords.x > 0 && edible_coords.y > 0 {
-                        Some(Coords {
-                            x: edible_coords.x - 1,
-                            y: edible_coords.y - 1,
-                        })
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            } else {
-                // move towards down (-1)
-                if edible_coords.x > original_cell.x {
-                    // this diagonal original_cell is on the right
-                    if edible_coords.x < CELL_N - 1 && edible_coords.y < CELL_N - 1 {
-                        Some(Coords {
-                            x: edible_coords.x + 1,
-                            y: edible_coords.y + 1,
-                        })
-                    } else {
-                        None
-                    }
-                } else if edible_coords.x < original_cell.x {
-                    // this diagonal original_cell is on the LEFT
-                    if edible_coords.x > 0 && edible_coords.y < CELL_N - 1 {
-                        Some(Coords {
-                            x: edible_coords.x - 1,
-                            y: edible_coords.y + 1,
-                        })
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
-            };
-            if let Some(landing) = landing_coords {
-                if grid[landing].is_none() {
-                    moves.push(Move::Capture {
-                        from: original_cell,
-                        to: landing,
-                        eat: edible_coords,
-                    })
-                }
-            }
-        });
+    let mut path = vec![original_cell];
+    let mut eaten = vec![];
+    capture_sequences(grid, piece, &mut path, &mut eaten, &mut moves);
 
     // rule: forced to capture if can capture
-    if !moves.is_empty() {
+    if rules.mandatory_capture() && !moves.is_empty() {
         return Ok(moves);
     }
 
-    original_cell
-        .diag()
-        .into_iter()
-        .filter(|cell| grid[*cell].is_none())
-        .for_each(|empty_coords| {
-            if player.direction == 1 {
-                // moves toward up (+1)
-                if empty_coords.y < original_cell.y {
-                    moves.push(Move::Simple {
-                        from: original_cell,
-                        to: empty_coords,
-                    });
-                }
-            } else {
-                // move towards down (-1)
-                if empty_coords.y > original_cell.y {
-                    moves.push(Move::Simple {
-                        from: original_cell,
-                        to: empty_coords,
-                    });
+    let flying = piece.is_king() && rules.flying_kings();
+    for (dx, dy) in move_directions(grid, &piece) {
+        let mut next = grid.step(original_cell, dx, dy);
+        while let Some(empty_coords) = next
+            && grid[empty_coords].is_none()
+        {
+            moves.push(Move::Simple {
+                from: original_cell,
+                to: empty_coords,
+            });
+            if !flying {
+                break;
+            }
+            next = grid.step(empty_coords, dx, dy);
+        }
+    }
+    Ok(moves)
+}
+
+/// recursively extends the capture sequence in `path`, pushing every complete sequence in `moves`.
+/// captured pieces stay on the board until the move is over, so they can't be jumped twice
+fn capture_sequences(
+    grid: &Board,
+    piece: Piece,
+    path: &mut Vec<Coords>,
+    eaten: &mut Vec<Coords>,
+    moves: &mut Vec<Move>,
+) {
+    let rules = grid.rules();
+    let current = *path.last().unwrap();
+    let origin = path[0];
+    // the moving piece already left its original cell
+    let is_empty = |cell: Coords| grid[cell].is_none() || cell == origin;
+    let flying = piece.is_king() && rules.flying_kings();
+    let mut extended = false;
+
+    for (dx, dy) in capture_directions(grid, &piece) {
+        // flying kings can reach the piece to capture from any distance
+        let mut edible = grid.step(current, dx, dy);
+        while flying
+            && let Some(cell) = edible
+            && is_empty(cell)
+        {
+            edible = grid.step(cell, dx, dy);
+        }
+        let Some(edible_coords) = edible else {
+            continue;
+        };
+        let Some(target) = grid[edible_coords] else {
+            continue;
+        };
+        if target.player_id == piece.player_id
+            || eaten.contains(&edible_coords)
+            || (target.is_king() && !piece.is_king() && !rules.pawns_capture_kings())
+        {
+            continue;
+        }
+
+        // and they can land on any empty cell after it
+        let mut landings = vec![];
+        let mut next = grid.step(edible_coords, dx, dy);
+        while let Some(landing) = next
+            && is_empty(landing)
+        {
+            landings.push(landing);
+            if !flying {
+                break;
+            }
+            next = grid.step(landing, dx, dy);
+        }
+
+        let mut sequences = vec![];
+        for landing in landings {
+            extended = true;
+            path.push(landing);
+            eaten.push(edible_coords);
+
+            let crowned = !piece.is_king() && landing.y == promotion_row(grid, piece.player_id);
+            match (crowned, rules.promotion()) {
+                // rule: a pawn crowned mid-sequence stops there
+                (true, Promotion::EndsMove) => sequences.push(Move::Capture {
+                    path: path.clone(),
+                    eat: eaten.clone(),
+                }),
+                // rule: a pawn crowned mid-sequence keeps capturing as a king
+                (true, Promotion::Immediate) => {
+                    let king = Piece::new(PieceType::King, piece.player_id);
+                    capture_sequences(grid, king, path, eaten, &mut sequences);
                 }
+                _ => capture_sequences(grid, piece, path, eaten, &mut sequences),
             }
+
+            path.pop();
+            eaten.pop();
+        }
+
+        // rule: a flying king must land where it can keep capturing, if there is such a cell
+        let continued = eaten.len() + 1;
+        if sequences.iter().any(|mv| mv.eaten().len() > continued) {
+            sequences.retain(|mv| mv.eaten().len() > continued);
+        }
+        moves.append(&mut sequences);
+    }
+
+    if !extended && !eaten.is_empty() {
+        moves.push(Move::Capture {
+            path: path.clone(),
+            eat: eaten.clone(),
         });
-    Ok(moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Variant;
+
+    const PLAYERS: [PlayerId; 2] = [1, 2];
+
+    /// a position from its FEN, white being the bottom player
+    fn position(fen: &str, variant: Variant) -> Board {
+        Board::from_fen(fen, variant, PLAYERS).unwrap().0
+    }
+    fn square(board: &Board, number: usize) -> Coords {
+        board.square_coords(number).unwrap()
+    }
+
+    #[test]
+    fn pawns_are_crowned_on_the_back_row() {
+        let mut board = position("W:W6:B32", Variant::American);
+        let moves = legal_moves(&board, PLAYERS[0]);
+        assert_eq!(moves.len(), 2);
+        let mv = moves[0].clone();
+        assert!(promotes(&board, &mv));
+        board.apply(&mv).unwrap();
+        assert!(board[mv.to()].is_some_and(|piece| piece.is_king()));
+
+        // the top player is crowned on the bottom row
+        let mut board = position("B:WK1:B27", Variant::American);
+        let moves = legal_moves(&board, PLAYERS[1]);
+        assert_eq!(moves.len(), 2);
+        board.apply(&moves[1]).unwrap();
+        assert!(board[moves[1].to()].is_some_and(|piece| piece.is_king()));
+    }
+
+    #[test]
+    fn pawns_only_move_forward() {
+        let board = position("W:W14:B32", Variant::American);
+        let moves = legal_moves(&board, PLAYERS[0]);
+        let targets = moves.iter().map(|mv| mv.to()).collect::<Vec<_>>();
+        assert_eq!(targets, vec![square(&board, 9), square(&board, 10)]);
+        assert!(moves.iter().all(|mv| !promotes(&board, mv)));
+    }
+
+    #[test]
+    fn kings_move_and_capture_both_ways() {
+        let board = position("W:WK14:B32", Variant::American);
+        assert_eq!(legal_moves(&board, PLAYERS[0]).len(), 4);
+
+        // a king captures backwards, where a pawn can't
+        let board = position("W:WK14:B18", Variant::American);
+        let moves = legal_moves(&board, PLAYERS[0]);
+        assert_eq!(
+            moves,
+            vec![Move::Capture {
+                path: vec![square(&board, 14), square(&board, 23)],
+                eat: vec![square(&board, 18)],
+            }]
+        );
+        let board = position("W:W14:B18", Variant::American);
+        assert!(
+            legal_moves(&board, PLAYERS[0])
+                .iter()
+                .all(|mv| !mv.is_capture())
+        );
+    }
+
+    #[test]
+    fn captures_chain_into_one_move() {
+        // after 30x23, the pawn can go on capturing either 19 or 18
+        let board = position("W:W30:B26,19,18", Variant::American);
+        let mut moves = legal_moves(&board, PLAYERS[0]);
+        moves.sort_by_key(|mv| board.square_number(mv.to()));
+        let chain = |eat: usize, to: usize| Move::Capture {
+            path: vec![square(&board, 30), square(&board, 23), square(&board, to)],
+            eat: vec![square(&board, 26), square(&board, eat)],
+        };
+        assert_eq!(moves, vec![chain(18, 14), chain(19, 16)]);
+
+        let mut board = board;
+        let record = board.play(&moves[1]).unwrap();
+        assert_eq!(record.captured.len(), 2);
+        assert!(board[square(&board, 16)].is_some());
+        assert!(board[square(&board, 26)].is_none() && board[square(&board, 19)].is_none());
+    }
+
+    #[test]
+    fn crowning_ends_the_capture() {
+        // the pawn crowned on 2 could capture 6 as a king, but its move stops there
+        let board = position("W:W11:B6,7", Variant::American);
+        let moves = legal_moves(&board, PLAYERS[0]);
+        assert_eq!(
+            moves,
+            vec![Move::Capture {
+                path: vec![square(&board, 11), square(&board, 2)],
+                eat: vec![square(&board, 7)],
+            }]
+        );
+        assert!(promotes(&board, &moves[0]));
+    }
+
+    #[test]
+    fn a_capture_anywhere_is_forced() {
+        // 21 could move quietly on its own, but 30 can capture
+        let board = position("W:W30,21:B26", Variant::American);
+        assert_eq!(
+            get_possible_moves(&board, square(&board, 21))
+                .unwrap()
+                .len(),
+            1
+        );
+        let moves = legal_moves(&board, PLAYERS[0]);
+        assert_eq!(
+            moves,
+            vec![Move::Capture {
+                path: vec![square(&board, 30), square(&board, 23)],
+                eat: vec![square(&board, 26)],
+            }]
+        );
+        // without a capture, every piece can move
+        let board = position("W:W30,21:B1", Variant::American);
+        assert_eq!(legal_moves(&board, PLAYERS[0]).len(), 3);
+        // and the other player isn't concerned
+        let board = position("B:W30,21:B26", Variant::American);
+        assert!(
+            legal_moves(&board, PLAYERS[1])
+                .iter()
+                .all(|mv| !mv.is_capture())
+        );
+    }
+
+    #[test]
+    fn a_player_without_moves_has_none() {
+        // the pawn is blocked by the edge of the board and the opponent's pawns
+        let board = position("W:W29:B25,22", Variant::American);
+        assert!(legal_moves(&board, PLAYERS[0]).is_empty());
+        let board = position("W:W29:B1", Variant::American);
+        assert_eq!(legal_moves(&board, PLAYERS[1]).len(), 2);
+    }
 }
@@ -1,11 +1,24 @@
+pub mod ballot;
+pub mod bitboard;
 pub mod board;
 pub mod coords;
+pub mod event_log;
+pub mod fen;
 pub mod game_state;
 pub mod game_utils;
+pub mod opening_book;
+pub mod pdn;
+pub mod perft;
 pub mod piece;
 pub mod player;
+pub mod rules;
+pub mod tablebase;
+pub mod transposition;
 pub mod utils;
+pub mod zobrist;
 
-pub static CELL_N: usize = 8;
+/// moves by each player in a row without a capture or a pawn move after which the game is
+/// drawn, so twice as many plies
+pub static DRAW_MOVE_LIMIT: usize = 50;
 pub static CHANNEL_ID: u8 = 0;
 pub static PROTOCOL_ID: u64 = 0;
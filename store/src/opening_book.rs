@@ -0,0 +1,293 @@
+//! Opening books: for the positions reached in the first moves of a collection of games, the
+//! moves played there, how often, and how the games went on to end.
+//!
+//! Positions are keyed by their zobrist hash as seen by the player to move, the board rotated
+//! if needed so that they sit at the bottom: a book built from games where black moves first
+//! also works for games where white does. The moves are stored the same way.
+use std::{collections::HashMap, fs, path::Path};
+
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::Board,
+    game_utils::{Move, legal_moves},
+    pdn::{PdnGame, parse_pdn},
+    player::PlayerId,
+    rules::Variant,
+};
+
+/// bumped whenever the book changes in a way older books can't be read with
+pub const BOOK_VERSION: u32 = 1;
+/// where books are usually saved
+pub const BOOK_FILE: &str = "book.bin";
+/// how many plies of each game go into a book by default
+pub const DEFAULT_PLIES: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookMove {
+    pub mv: Move,
+    /// the games the move was played in, which makes it more likely to be picked
+    pub weight: u32,
+    /// how those games ended for the player making the move; unfinished games count for none
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+impl BookMove {
+    /// the average result of the move for the player making it, a draw counting as half a win
+    pub fn score(&self) -> Option<f64> {
+        let games = self.wins + self.draws + self.losses;
+        (games > 0).then(|| (self.wins as f64 + self.draws as f64 / 2.0) / games as f64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpeningBook {
+    pub version: u32,
+    pub variant: Variant,
+    positions: HashMap<u64, Vec<BookMove>>,
+}
+
+impl OpeningBook {
+    pub fn new(variant: Variant) -> OpeningBook {
+        OpeningBook {
+            version: BOOK_VERSION,
+            variant,
+            positions: HashMap::new(),
+        }
+    }
+
+    /// a book of the games of `variant` in a PDN collection, games of other variants skipped
+    pub fn from_pdn(text: &str, variant: Variant, plies: usize) -> Result<OpeningBook, String> {
+        let mut book = OpeningBook::new(variant);
+        for game in parse_pdn(text)? {
+            if game.variant() == variant {
+                book.add_game(&game, plies)?;
+            }
+        }
+        Ok(book)
+    }
+
+    /// adds the first `plies` moves of the game
+    pub fn add_game(&mut self, game: &PdnGame, plies: usize) -> Result<(), String> {
+        let (board, mut turn) = game.initial_position();
+        let mut board = board.clone();
+        if board.variant() != self.variant {
+            return Err(format!(
+                "A {} game can't go in a {} book",
+                board.variant(),
+                self.variant
+            ));
+        }
+        let [white, black] = board.players();
+        let winner = match game.result() {
+            "1-0" => Some(Some(white)),
+            "0-1" => Some(Some(black)),
+            "1/2-1/2" => Some(None),
+            _ => None,
+        };
+
+        for mv in game.moves().iter().take(plies) {
+            let (key, oriented) = oriented(&board, turn, mv);
+            let moves = self.positions.entry(key).or_default();
+            let index = match moves.iter().position(|book_move| book_move.mv == oriented) {
+                Some(index) => index,
+                None => {
+                    moves.push(BookMove {
+                        mv: oriented,
+                        weight: 0,
+                        wins: 0,
+                        draws: 0,
+                        losses: 0,
+                    });
+                    moves.len() - 1
+                }
+            };
+            let book_move = &mut moves[index];
+            book_move.weight += 1;
+            match winner {
+                Some(Some(winner)) if winner == turn => book_move.wins += 1,
+                Some(Some(_)) => book_move.losses += 1,
+                Some(None) => book_move.draws += 1,
+                None => {}
+            }
+
+            board.apply(mv)?;
+            turn = if turn == white { black } else { white };
+        }
+        Ok(())
+    }
+
+    /// the positions in the book
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// the book moves of `player_id` in this position, most played first
+    pub fn moves(&self, board: &Board, player_id: PlayerId) -> Vec<BookMove> {
+        if board.variant() != self.variant {
+            return vec![];
+        }
+        let rotated = player_id != board.players()[0];
+        let key = if rotated {
+            board.rotated().hash()
+        } else {
+            board.hash()
+        };
+        let legal = legal_moves(board, player_id);
+        let mut moves = self
+            .positions
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .map(|book_move| BookMove {
+                mv: if rotated {
+                    book_move.mv.map_coords(|coords| board.rotate(coords))
+                } else {
+                    book_move.mv.clone()
+                },
+                ..book_move.clone()
+            })
+            // positions sharing a hash are rare, but their moves are probably illegal here
+            .filter(|book_move| legal.contains(&book_move.mv))
+            .collect::<Vec<_>>();
+        moves.sort_by_key(|book_move| std::cmp::Reverse(book_move.weight));
+        moves
+    }
+
+    /// a book move at random, the most played ones more likely
+    pub fn pick(&self, board: &Board, player_id: PlayerId) -> Option<Move> {
+        let moves = self.moves(board, player_id);
+        let total = moves.iter().map(|book_move| book_move.weight).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+        let mut choice = SmallRng::from_entropy().gen_range(0..total);
+        moves.into_iter().find_map(|book_move| {
+            if choice < book_move.weight {
+                Some(book_move.mv)
+            } else {
+                choice -= book_move.weight;
+                None
+            }
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        postcard::to_allocvec(self).map_err(|err| format!("Cannot serialize the book: {err}"))
+    }
+    pub fn from_bytes(bytes: &[u8]) -> Result<OpeningBook, String> {
+        // the version comes first, so that it can be checked before reading the rest
+        let (version, _) = postcard::take_from_bytes::<u32>(bytes)
+            .map_err(|err| format!("Not an opening book: {err}"))?;
+        if version != BOOK_VERSION {
+            return Err(format!(
+                "Unsupported opening book version {version}, expected {BOOK_VERSION}"
+            ));
+        }
+        postcard::from_bytes(bytes).map_err(|err| format!("Corrupted opening book: {err}"))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_bytes()?)
+            .map_err(|err| format!("Cannot write {}: {err}", path.display()))
+    }
+    pub fn load(path: &Path) -> Result<OpeningBook, String> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// the key of the position and the move, both rotated if the player moving sits at the top
+fn oriented(board: &Board, player_id: PlayerId, mv: &Move) -> (u64, Move) {
+    if player_id == board.players()[0] {
+        (board.hash(), mv.clone())
+    } else {
+        (
+            board.rotated().hash(),
+            mv.map_coords(|coords| board.rotate(coords)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdn::notation;
+
+    const WHITE: PlayerId = 1;
+    const BLACK: PlayerId = 2;
+    // as on a standard board, black at the top moving first
+    const GAMES: &str = "[GameType \"21\"]\n[Result \"1-0\"]\n1. 9-13 22-18 2. 10-15 1-0\n\n\
+        [GameType \"21\"]\n[Result \"0-1\"]\n1. 9-13 22-17 0-1\n\n\
+        [GameType \"21\"]\n[Result \"1/2-1/2\"]\n1. 11-15 23-19 1/2-1/2\n";
+
+    fn book() -> OpeningBook {
+        OpeningBook::from_pdn(GAMES, Variant::American, DEFAULT_PLIES).unwrap()
+    }
+    fn book_moves(book: &OpeningBook, board: &Board, player_id: PlayerId) -> Vec<String> {
+        book.moves(board, player_id)
+            .iter()
+            .map(|book_move| notation(board, &book_move.mv))
+            .collect()
+    }
+    /// plays the book move written `written` on the board
+    fn play(book: &OpeningBook, board: &mut Board, player_id: PlayerId, written: &str) {
+        let book_move = book
+            .moves(board, player_id)
+            .into_iter()
+            .find(|book_move| notation(board, &book_move.mv) == written);
+        board.apply(&book_move.unwrap().mv).unwrap();
+    }
+
+    #[test]
+    fn moves_are_found_for_the_player_at_the_top() {
+        let book = book();
+        let (mut board, turn) =
+            Board::from_fen("B:W21-32:B1-12", Variant::American, [WHITE, BLACK]).unwrap();
+        assert_eq!(turn, BLACK);
+        assert_eq!(book_moves(&book, &board, BLACK), ["9-13", "11-15"]);
+        let moves = book.moves(&board, BLACK);
+        assert_eq!((moves[0].weight, moves[0].wins, moves[0].losses), (2, 1, 1));
+        assert_eq!(moves[1].score(), Some(0.5));
+
+        let mv = book.pick(&board, BLACK).unwrap();
+        assert!(["9-13", "11-15"].contains(&notation(&board, &mv).as_str()));
+        play(&book, &mut board, BLACK, "9-13");
+        assert_eq!(book_moves(&book, &board, WHITE), ["22-18", "22-17"]);
+        assert_eq!(book.pick(&board, BLACK), None);
+    }
+
+    #[test]
+    fn moves_are_found_on_rotated_boards() {
+        // as the server seats them, the player moving first at the bottom
+        let book = book();
+        let mut board = Board::starting_position(Variant::American, [BLACK, WHITE]);
+        assert_eq!(book_moves(&book, &board, BLACK), ["24-20", "22-18"]);
+        for _ in 0..10 {
+            let mv = book.pick(&board, BLACK).unwrap();
+            assert!(["24-20", "22-18"].contains(&notation(&board, &mv).as_str()));
+        }
+
+        play(&book, &mut board, BLACK, "24-20");
+        assert_eq!(book_moves(&book, &board, WHITE), ["11-15", "11-16"]);
+        let mv = book.pick(&board, WHITE).unwrap();
+        assert!(legal_moves(&board, WHITE).contains(&mv));
+        play(&book, &mut board, WHITE, "11-15");
+        assert_eq!(book_moves(&book, &board, BLACK), ["23-18"]);
+    }
+
+    #[test]
+    fn books_of_other_variants_have_no_moves() {
+        let book = book();
+        let board = Board::starting_position(Variant::International, [WHITE, BLACK]);
+        assert_eq!(book.pick(&board, WHITE), None);
+        let bytes = book.to_bytes().unwrap();
+        assert_eq!(OpeningBook::from_bytes(&bytes).unwrap().len(), book.len());
+    }
+}
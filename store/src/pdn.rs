@@ -0,0 +1,464 @@
+//! Game records in PDN (Portable Draughts Notation): a list of `[Tag "value"]` headers
+//! followed by the moves, e.g. `1. 11-15 23-19 2. 8-11 22x15 *`.
+//! Square numbers follow `Board::square_number`, and positions use the FEN format of `fen.rs`.
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    board::{Board, Position},
+    game_state::{EndGameReason, GameEvent, GameState},
+    game_utils::Move,
+    player::PlayerId,
+    rules::Variant,
+};
+
+// ids of the white (bottom) and black (top) player when replaying imported games
+const WHITE: PlayerId = 1;
+const BLACK: PlayerId = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdnGame {
+    headers: Vec<(String, String)>,
+    moves: Vec<Move>,
+    // the position of the `FEN` header, or the variant's starting one, with the player to
+    // move: checked when the game is built, so that it can always be written
+    initial_position: (Board, PlayerId),
+}
+
+impl PdnGame {
+    /// the record of a game, finished or not, with the given date (`YYYY.MM.DD`).
+    /// the server seats the player moving first at the bottom, where other programs expect
+    /// white: games from the starting position are turned around so that the side moving first
+    /// is the one starting games of the variant, black on squares 1 to 12 in american checkers
+    pub fn from_game(state: &GameState, date: &str) -> PdnGame {
+        let (board, initial_turn) = state.initial_position();
+        let variant = board.variant();
+        let rotate = *board == Board::starting_position(variant, board.players())
+            && (initial_turn == board.players()[0]) != (first_to_move(variant) == WHITE);
+        let (initial_board, moves) = if rotate {
+            let moves = state
+                .moves()
+                .map(|mv| mv.map_coords(|coords| board.rotate(coords)));
+            (board.rotated(), moves.collect())
+        } else {
+            (board.clone(), state.moves().cloned().collect())
+        };
+
+        let [white, black] = initial_board.players();
+        let name = |player_id: PlayerId| {
+            state
+                .initial_players()
+                .get(&player_id)
+                .map_or("?".to_string(), |player| player.name.clone())
+        };
+        let result = state.history().iter().rev().find_map(|event| match event {
+            GameEvent::EndGame { reason } => Some(match reason {
+                EndGameReason::PlayerWon { winner } if *winner == white => "1-0",
+                EndGameReason::PlayerWon { .. } => "0-1",
+                EndGameReason::PlayerLeft { player_id } if *player_id == white => "0-1",
+                EndGameReason::PlayerLeft { .. } => "1-0",
+                EndGameReason::Draw { .. } => "1/2-1/2",
+            }),
+            _ => None,
+        });
+
+        PdnGame {
+            headers: vec![
+                ("Event".to_string(), "checkeRS game".to_string()),
+                ("Date".to_string(), date.to_string()),
+                ("White".to_string(), name(white)),
+                ("Black".to_string(), name(black)),
+                ("Result".to_string(), result.unwrap_or("*").to_string()),
+                ("GameType".to_string(), game_type(variant).to_string()),
+                ("FEN".to_string(), initial_board.to_fen(initial_turn)),
+            ],
+            moves,
+            initial_position: (initial_board, initial_turn),
+        }
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+    /// replaces the value of a header, or adds it; the moves depend on the `GameType` and
+    /// `FEN` headers, so those can't be changed
+    pub fn set_header(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if ["GameType", "FEN"].contains(&name) {
+            return Err(format!("The {name} header of a game can't be changed"));
+        }
+        match self.headers.iter_mut().find(|(header, _)| header == name) {
+            Some((_, previous)) => *previous = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.initial_position.0.variant()
+    }
+
+    /// the position the game starts from, with the player to move; white is the player at the
+    /// bottom of the board, `WHITE` in parsed games
+    pub fn initial_position(&self) -> (&Board, PlayerId) {
+        (&self.initial_position.0, self.initial_position.1)
+    }
+
+    pub fn result(&self) -> &str {
+        self.header("Result").unwrap_or("*")
+    }
+}
+
+impl fmt::Display for PdnGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, value) in self.headers.iter() {
+            writeln!(f, "[{name} \"{}\"]", value.replace('"', "'"))?;
+        }
+        writeln!(f)?;
+
+        // square numbers only depend on the board's size, not on the pieces
+        let (board, mut turn) = self.initial_position();
+        let [white, black] = board.players();
+        // moves are numbered from the side that starts games of this variant
+        let leader = if first_to_move(board.variant()) == WHITE {
+            white
+        } else {
+            black
+        };
+        let mut tokens = vec![];
+        let mut move_number = 1;
+        if turn != leader {
+            tokens.push(format!("{move_number}..."));
+        }
+        for mv in self.moves.iter() {
+            if turn == leader {
+                tokens.push(format!("{move_number}."));
+            } else {
+                move_number += 1;
+            }
+            tokens.push(notation(board, mv));
+            turn = if turn == white { black } else { white };
+        }
+        tokens.push(self.result().to_string());
+
+        // keep lines short, as most draughts programs expect
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                writeln!(f, "{line}")?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{line}")
+    }
+}
+
+/// the move as square numbers: `11-15` for simple moves, `11x18x25` for captures
+pub fn notation(board: &Board, mv: &Move) -> String {
+    let separator = if mv.is_capture() { "x" } else { "-" };
+    mv.path()
+        .iter()
+        .map(|coords| {
+            board
+                .square_number(*coords)
+                .map_or("?".to_string(), |n| n.to_string())
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// finds the legal move of `player_id` written as `token`: every square in the notation has
+/// to be on the move's path, so both `11x25` and `11x18x25` describe the same capture
+pub fn parse_move(board: &Board, player_id: PlayerId, token: &str) -> Result<Move, String> {
+    let squares = token
+        .split(['-', 'x', ':'])
+        .map(|square| square.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("Invalid move: {token}"))?;
+    if squares.len() < 2 {
+        return Err(format!("Invalid move: {token}"));
+    }
+    board
+        .legal_moves(player_id)
+        .into_iter()
+        .find(|mv| {
+            let path = mv
+                .path()
+                .iter()
+                .filter_map(|coords| board.square_number(*coords))
+                .collect::<Vec<_>>();
+            path.first() == squares.first()
+                && path.last() == squares.last()
+                && squares.iter().all(|square| path.contains(square))
+        })
+        .ok_or(format!("Illegal move: {token}"))
+}
+
+/// parses a PDN file holding any number of games, replaying them to find the moves
+pub fn parse_pdn(text: &str) -> Result<Vec<PdnGame>, String> {
+    let mut games = vec![];
+    let mut headers = vec![];
+    let mut tokens = vec![];
+    let mut chars = text.chars();
+    let mut token = String::new();
+    // comments and variations are skipped
+    let mut depth = 0;
+
+    let flush = |token: &mut String, tokens: &mut Vec<String>| {
+        if !token.is_empty() {
+            tokens.push(std::mem::take(token));
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                flush(&mut token, &mut tokens);
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            '(' => {
+                flush(&mut token, &mut tokens);
+                depth += 1;
+            }
+            ')' => depth -= 1,
+            _ if depth > 0 => {}
+            '[' => {
+                flush(&mut token, &mut tokens);
+                // a header after some moves starts a new game
+                if !tokens.is_empty() {
+                    games.push(read_game(std::mem::take(&mut headers), &tokens)?);
+                    tokens.clear();
+                }
+                let header = chars.by_ref().take_while(|c| *c != ']').collect::<String>();
+                let header = header.trim();
+                let (name, value) = header.split_once(' ').unwrap_or((header, ""));
+                headers.push((name.to_string(), value.trim().trim_matches('"').to_string()));
+            }
+            c if c.is_whitespace() => flush(&mut token, &mut tokens),
+            c => token.push(c),
+        }
+    }
+    flush(&mut token, &mut tokens);
+    if !tokens.is_empty() || !headers.is_empty() {
+        games.push(read_game(headers, &tokens)?);
+    }
+    Ok(games)
+}
+
+fn read_game(headers: Vec<(String, String)>, tokens: &[String]) -> Result<PdnGame, String> {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    };
+    // american checkers if there is no game type, which can be followed by board details,
+    // e.g. `20,W,10,10,N2,0`
+    let variant = match header("GameType") {
+        None => Variant::American,
+        Some(game_type) => {
+            let number = game_type.split(',').next().unwrap_or_default().trim();
+            Variant::ALL
+                .into_iter()
+                .find(|variant| self::game_type(*variant).to_string() == number)
+                .ok_or(format!("Unsupported game type: {game_type}"))?
+        }
+    };
+    let (mut board, mut turn) = match header("FEN") {
+        Some(fen) => Board::from_fen(fen, variant, [WHITE, BLACK])?,
+        None => (
+            Board::starting_position(variant, [WHITE, BLACK]),
+            first_to_move(variant),
+        ),
+    };
+
+    let mut game = PdnGame {
+        initial_position: (board.clone(), turn),
+        headers,
+        moves: vec![],
+    };
+    for token in tokens {
+        // move numbers (`1.`, `12...`) may be glued to the move itself
+        let token = token.rsplit('.').next().unwrap_or_default();
+        let token = token.trim_end_matches(['!', '?', '*']);
+        if token.is_empty() || ["1-0", "0-1", "2-0", "0-2", "1-1", "1/2-1/2"].contains(&token) {
+            continue;
+        }
+        let mv = parse_move(&board, turn, token)?;
+        board.apply(&mv)?;
+        game.moves.push(mv);
+        turn = if turn == WHITE { BLACK } else { WHITE };
+    }
+    Ok(game)
+}
+
+/// the `GameType` number of each variant in the PDN standard
+pub fn game_type(variant: Variant) -> u32 {
+    match variant {
+        Variant::International => 20,
+        Variant::American => 21,
+        Variant::Italian => 22,
+        Variant::Pool => 23,
+        Variant::Russian => 25,
+        Variant::Brazilian => 26,
+        Variant::Canadian => 27,
+    }
+}
+
+/// who plays first when a game has no FEN: black in american and pool checkers, white otherwise
+fn first_to_move(variant: Variant) -> PlayerId {
+    match variant {
+        Variant::American | Variant::Pool => BLACK,
+        _ => WHITE,
+    }
+}
+
+/// today's date as `YYYY.MM.DD`, the format of the `Date` header
+pub fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86400) as i64;
+    // civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}.{month:02}.{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{game_utils::legal_moves, player::Player};
+
+    /// a game as the server starts it, the first player `10` at the bottom of the board
+    fn server_game(variant: Variant) -> GameState {
+        let players = [(10, "first", 1), (20, "second", -1)]
+            .into_iter()
+            .map(|(id, name, direction)| {
+                let player = Player {
+                    id,
+                    name: name.to_string(),
+                    direction,
+                    score: 0,
+                };
+                (id, player)
+            })
+            .collect::<HashMap<_, _>>();
+        GameState::new(players, 10, variant)
+    }
+    /// plays the first legal move of each player in turn
+    fn play(state: &mut GameState, plies: usize) {
+        for _ in 0..plies {
+            let player_id = state.is_turn;
+            let mv = legal_moves(&state.grid, player_id).remove(0);
+            state.dispatch(&GameEvent::Move { mv, player_id }).unwrap();
+            let player_id = if player_id == 10 { 20 } else { 10 };
+            state
+                .dispatch(&GameEvent::TurnChanged { player_id })
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn american_games_are_exported_with_black_moving_first() {
+        let mut state = server_game(Variant::American);
+        play(&mut state, 6);
+        let record = PdnGame::from_game(&state, "2024.01.01");
+        assert_eq!(record.header("Black"), Some("first"));
+        assert_eq!(record.header("White"), Some("second"));
+        assert_eq!(
+            record.header("FEN"),
+            Some("B:W21,22,23,24,25,26,27,28,29,30,31,32:B1,2,3,4,5,6,7,8,9,10,11,12")
+        );
+        let text = record.to_string();
+        let first_move = text.lines().last().unwrap().split(' ').nth(1).unwrap();
+        let black_openings = ["9-13", "9-14", "10-14", "10-15", "11-15", "11-16", "12-16"];
+        assert!(text.lines().last().unwrap().starts_with("1. "), "{text}");
+        assert!(black_openings.contains(&first_move), "{text}");
+    }
+
+    #[test]
+    fn international_games_keep_white_moving_first() {
+        let mut state = server_game(Variant::International);
+        play(&mut state, 4);
+        let record = PdnGame::from_game(&state, "2024.01.01");
+        assert_eq!(record.header("White"), Some("first"));
+        assert!(record.header("FEN").unwrap().starts_with("W:W31,"));
+        assert_eq!(record.moves(), state.moves().cloned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn exported_games_import_back() {
+        for variant in Variant::ALL {
+            let mut state = server_game(variant);
+            play(&mut state, 10);
+            state
+                .dispatch(&GameEvent::EndGame {
+                    reason: EndGameReason::PlayerWon { winner: 10 },
+                })
+                .unwrap();
+            let record = PdnGame::from_game(&state, "2024.01.01");
+            let games = parse_pdn(&record.to_string()).unwrap();
+            assert_eq!(games.len(), 1, "{variant}");
+            assert_eq!(games[0].headers(), record.headers(), "{variant}");
+            assert_eq!(games[0].moves(), record.moves(), "{variant}");
+            assert_eq!(games[0].variant(), variant);
+            assert_eq!(games[0].to_string(), record.to_string(), "{variant}");
+        }
+    }
+
+    #[test]
+    fn imported_games_export_back() {
+        let text = "[Event \"club\"]\n[Result \"*\"]\n\n1. 11-15 23-19 2. 8-11 22-17 {quiet} *\n";
+        let games = parse_pdn(text).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].moves().len(), 4);
+        assert_eq!(games[0].to_string(), text.replace(" {quiet}", ""));
+
+        // a game starting with white to move, the squares a capture goes through left out
+        let text = "[GameType \"21\"]\n[FEN \"W:W23:B18,10\"]\n\n1... 23x7 *\n";
+        let games = parse_pdn(text).unwrap();
+        assert_eq!(games[0].moves()[0].eaten().len(), 2);
+        assert_eq!(games[0].to_string(), text.replace("23x7", "23x14x7"));
+        assert!(parse_pdn("[FEN \"W:W23:B18\"]\n1... 23-19 *").is_err());
+        assert!(parse_pdn("[GameType \"99\"]\n1. 11-15 *").is_err());
+    }
+
+    #[test]
+    fn headers_the_moves_depend_on_stay_as_built() {
+        let mut record = PdnGame::from_game(&server_game(Variant::American), "2024.01.01");
+        record.set_header("Event", "match").unwrap();
+        record.set_header("Round", "3").unwrap();
+        assert_eq!(record.header("Event"), Some("match"));
+        assert_eq!(record.header("Round"), Some("3"));
+        assert!(record.set_header("FEN", "not a position").is_err());
+        assert!(record.set_header("GameType", "20").is_err());
+        assert!(record.to_string().ends_with("\n*\n"));
+    }
+}
@@ -0,0 +1,47 @@
+//! Perft: counts the positions reached after every sequence of legal moves of a given length,
+//! to check move generation against numbers computed by other programs.
+use crate::{board::Position, game_utils::Move, player::PlayerId};
+
+fn opponent<P: Position>(position: &P, player_id: PlayerId) -> PlayerId {
+    let [bottom, top] = position.players();
+    if player_id == bottom { top } else { bottom }
+}
+
+/// the number of positions reached after `depth` moves, `player_id` moving first
+pub fn perft<P: Position>(position: &P, player_id: PlayerId, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = position.legal_moves(player_id);
+    // the last moves don't need to be played to be counted
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let next_player = opponent(position, player_id);
+    moves
+        .iter()
+        .map(|mv| {
+            let mut next = position.clone();
+            next.apply(mv).unwrap();
+            perft(&next, next_player, depth - 1)
+        })
+        .sum()
+}
+
+/// `perft` split by first move, to find which one is wrong when the total does not match
+pub fn divide<P: Position>(position: &P, player_id: PlayerId, depth: usize) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return vec![];
+    }
+    let next_player = opponent(position, player_id);
+    position
+        .legal_moves(player_id)
+        .into_iter()
+        .map(|mv| {
+            let mut next = position.clone();
+            next.apply(&mv).unwrap();
+            let count = perft(&next, next_player, depth - 1);
+            (mv, count)
+        })
+        .collect()
+}
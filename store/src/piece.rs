@@ -1,10 +1,12 @@
-#[derive(Debug, Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PieceType {
     Pawn,
     King,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Piece {
     pub piece_type: PieceType,
     pub player_id: u64,
@@ -12,8 +14,11 @@ pub struct Piece {
 impl Piece {
     pub fn new(piece_type: PieceType, player: u64) -> Self {
         Piece {
-            piece_type: piece_type,
+            piece_type,
             player_id: player,
         }
     }
+    pub fn is_king(&self) -> bool {
+        self.piece_type == PieceType::King
+    }
 }
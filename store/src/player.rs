@@ -27,7 +27,7 @@ impl Player {
                 self.name
             )
             .fg(name_color),
-            format!(" score:").white(),
+            " score:".white(),
             format!(" {}", self.score).white().bold(),
         ])
     }
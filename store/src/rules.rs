@@ -0,0 +1,443 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{board::Board, coords::Coords, game_utils::Move};
+
+/// when a pawn reaching the opponent's back row during a capture gets crowned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Promotion {
+    /// the pawn is crowned and the capture sequence stops there
+    EndsMove,
+    /// the pawn is crowned and keeps capturing as a king
+    Immediate,
+    /// the pawn keeps capturing as a pawn, and is only crowned if the move ends on the back row
+    AtEndOfMove,
+}
+
+/// the rules that change between checkers variants
+pub trait RuleSet: fmt::Debug + Send + Sync {
+    /// cells on each side of the (square) board
+    fn board_size(&self) -> usize;
+    /// rows filled with pawns for each player at the start of the game
+    fn starting_rows(&self) -> usize;
+    /// whether pieces can stand on this cell; by default the bottom-left corner is playable
+    fn is_playable(&self, coords: Coords) -> bool {
+        (coords.x + coords.y) % 2 == (self.board_size() - 1) % 2
+    }
+    /// whether pawns can capture backwards as well as forwards
+    fn pawns_capture_backwards(&self) -> bool;
+    /// whether pawns are allowed to capture kings
+    fn pawns_capture_kings(&self) -> bool {
+        true
+    }
+    /// whether kings move and capture any distance along a diagonal, or one cell at a time
+    fn flying_kings(&self) -> bool;
+    /// whether a player has to capture when they can
+    fn mandatory_capture(&self) -> bool {
+        true
+    }
+    /// whether a player has to take the sequence capturing the most pieces
+    fn majority_capture(&self) -> bool;
+    fn promotion(&self) -> Promotion;
+
+    /// narrows down the capture sequences available to a player to the ones they are allowed to
+    /// play, by default keeping only the longest ones when majority capture applies
+    fn filter_captures(&self, _grid: &Board, captures: Vec<Move>) -> Vec<Move> {
+        if !self.majority_capture() {
+            return captures;
+        }
+        let most = captures
+            .iter()
+            .map(|mv| mv.eaten().len())
+            .max()
+            .unwrap_or(0);
+        captures
+            .into_iter()
+            .filter(|mv| mv.eaten().len() == most)
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Variant {
+    #[default]
+    American,
+    International,
+    Russian,
+    Brazilian,
+    Italian,
+    Pool,
+    Canadian,
+}
+impl Variant {
+    pub const ALL: [Variant; 7] = [
+        Variant::American,
+        Variant::International,
+        Variant::Russian,
+        Variant::Brazilian,
+        Variant::Italian,
+        Variant::Pool,
+        Variant::Canadian,
+    ];
+
+    pub fn rules(&self) -> &'static dyn RuleSet {
+        match self {
+            Variant::American => &American,
+            Variant::International => &International,
+            Variant::Russian => &Russian,
+            Variant::Brazilian => &Brazilian,
+            Variant::Italian => &Italian,
+            Variant::Pool => &Pool,
+            Variant::Canadian => &Canadian,
+        }
+    }
+}
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Variant::American => "american",
+            Variant::International => "international",
+            Variant::Russian => "russian",
+            Variant::Brazilian => "brazilian",
+            Variant::Italian => "italian",
+            Variant::Pool => "pool",
+            Variant::Canadian => "canadian",
+        };
+        write!(f, "{name}")
+    }
+}
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Variant::ALL
+            .into_iter()
+            .find(|variant| variant.to_string() == s.to_lowercase())
+            .ok_or(format!("Unknown variant: {s}"))
+    }
+}
+
+/// American/English checkers: pawns capture forward only, kings move one cell at a time
+#[derive(Debug)]
+pub struct American;
+impl RuleSet for American {
+    fn board_size(&self) -> usize {
+        8
+    }
+    fn starting_rows(&self) -> usize {
+        3
+    }
+    fn pawns_capture_backwards(&self) -> bool {
+        false
+    }
+    fn flying_kings(&self) -> bool {
+        false
+    }
+    fn majority_capture(&self) -> bool {
+        false
+    }
+    fn promotion(&self) -> Promotion {
+        Promotion::EndsMove
+    }
+}
+
+/// International draughts, on a 10x10 board with flying kings and majority capture
+#[derive(Debug)]
+pub struct International;
+impl RuleSet for International {
+    fn board_size(&self) -> usize {
+        10
+    }
+    fn starting_rows(&self) -> usize {
+        4
+    }
+    fn pawns_capture_backwards(&self) -> bool {
+        true
+    }
+    fn flying_kings(&self) -> bool {
+        true
+    }
+    fn majority_capture(&self) -> bool {
+        true
+    }
+    fn promotion(&self) -> Promotion {
+        Promotion::AtEndOfMove
+    }
+}
+
+/// Russian draughts: like pool checkers, but a pawn is crowned as soon as it touches the back row
+#[derive(Debug)]
+pub struct Russian;
+impl RuleSet for Russian {
+    fn board_size(&self) -> usize {
+        8
+    }
+    fn starting_rows(&self) -> usize {
+        3
+    }
+    fn pawns_capture_backwards(&self) -> bool {
+        true
+    }
+    fn flying_kings(&self) -> bool {
+        true
+    }
+    fn majority_capture(&self) -> bool {
+        false
+    }
+    fn promotion(&self) -> Promotion {
+        Promotion::Immediate
+    }
+}
+
+/// Brazilian draughts: the international rules played on an 8x8 board
+#[derive(Debug)]
+pub struct Brazilian;
+impl RuleSet for Brazilian {
+    fn board_size(&self) -> usize {
+        8
+    }
+    fn starting_rows(&self) -> usize {
+        3
+    }
+    fn pawns_capture_backwards(&self) -> bool {
+        true
+    }
+    fn flying_kings(&self) -> bool {
+        true
+    }
+    fn majority_capture(&self) -> bool {
+        true
+    }
+    fn promotion(&self) -> Promotion {
+        Promotion::AtEndOfMove
+    }
+}
+
+/// Italian draughts: pawns can't capture kings, and captures follow a strict order of priority
+#[derive(Debug)]
+pub struct Italian;
+impl RuleSet for Italian {
+    fn board_size(&self) -> usize {
+        8
+    }
+    fn starting_rows(&self) -> usize {
+        3
+    }
+    // the board is mirrored: the bottom-right corner is playable
+    fn is_playable(&self, coords: Coords) -> bool {
+        (coords.x + coords.y).is_multiple_of(2)
+    }
+    fn pawns_capture_backwards(&self) -> bool {
+        false
+    }
+    fn pawns_capture_kings(&self) -> bool {
+        false
+    }
+    fn flying_kings(&self) -> bool {
+        false
+    }
+    fn majority_capture(&self) -> bool {
+        true
+    }
+    fn promotion(&self) -> Promotion {
+        Promotion::EndsMove
+    }
+
+    /// the player must capture the most pieces, then capture with a king, then capture
+    /// the most kings, and then capture kings as early as possible in the sequence
+    fn filter_captures(&self, grid: &Board, captures: Vec<Move>) -> Vec<Move> {
+        let priority = |mv: &Move| -> (usize, bool, usize, Vec<bool>) {
+            let Move::Capture { path, eat } = mv else {
+                return (0, false, 0, vec![]);
+            };
+            let kings = eat
+                .iter()
+                .map(|coords| grid[*coords].is_some_and(|p| p.is_king()))
+                .collect::<Vec<_>>();
+            (
+                eat.len(),
+                grid[path[0]].is_some_and(|p| p.is_king()),
+                kings.iter().filter(|king| **king).count(),
+                kings,
+            )
+        };
+        let Some(best) = captures.iter().map(priority).max() else {
+            return captures;
+        };
+        captures
+            .into_iter()
+            .filter(|mv| priority(mv) == best)
+            .collect()
+    }
+}
+
+/// Pool checkers: flying kings and backward captures, but no majority rule
+#[derive(Debug)]
+pub struct Pool;
+impl RuleSet for Pool {
+    fn board_size(&self) -> usize {
+        8
+    }
+    fn starting_rows(&self) -> usize {
+        3
+    }
+    fn pawns_capture_backwards(&self) -> bool {
+        true
+    }
+    fn flying_kings(&self) -> bool {
+        true
+    }
+    fn majority_capture(&self) -> bool {
+        false
+    }
+    fn promotion(&self) -> Promotion {
+        Promotion::AtEndOfMove
+    }
+}
+
+/// Canadian checkers: the international rules played on a 12x12 board
+#[derive(Debug)]
+pub struct Canadian;
+impl RuleSet for Canadian {
+    fn board_size(&self) -> usize {
+        12
+    }
+    fn starting_rows(&self) -> usize {
+        5
+    }
+    fn pawns_capture_backwards(&self) -> bool {
+        true
+    }
+    fn flying_kings(&self) -> bool {
+        true
+    }
+    fn majority_capture(&self) -> bool {
+        true
+    }
+    fn promotion(&self) -> Promotion {
+        Promotion::AtEndOfMove
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        game_utils::{legal_moves, promotes},
+        player::PlayerId,
+    };
+
+    const PLAYERS: [PlayerId; 2] = [1, 2];
+
+    /// the moves of the player to move in a FEN position, white being the bottom player
+    fn moves(fen: &str, variant: Variant) -> (Board, Vec<Move>) {
+        let (board, turn) = Board::from_fen(fen, variant, PLAYERS).unwrap();
+        let moves = legal_moves(&board, turn);
+        (board, moves)
+    }
+    /// the squares a move goes through, as numbered in draughts notation
+    fn squares(board: &Board, mv: &Move) -> Vec<usize> {
+        mv.path()
+            .iter()
+            .map(|coords| board.square_number(*coords).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn variants_round_trip_through_their_name() {
+        for variant in Variant::ALL {
+            assert_eq!(variant.to_string().parse::<Variant>(), Ok(variant));
+        }
+        assert!("checkers".parse::<Variant>().is_err());
+    }
+
+    #[test]
+    fn capture_is_mandatory() {
+        for variant in [Variant::American, Variant::Russian, Variant::Pool] {
+            let (_, moves) = moves("W:W30,21:B26", variant);
+            assert!(moves.iter().all(|mv| mv.is_capture()), "{variant}");
+        }
+    }
+
+    #[test]
+    fn majority_capture_keeps_the_longest_sequences() {
+        // 21x14 takes one piece, 30x23x16 two
+        let fen = "W:W30,21:B26,19,17";
+        let (_, american) = moves(fen, Variant::American);
+        assert_eq!(american.len(), 2);
+        let (board, brazilian) = moves(fen, Variant::Brazilian);
+        assert_eq!(brazilian.len(), 1);
+        assert_eq!(squares(&board, &brazilian[0]), vec![30, 23, 16]);
+    }
+
+    #[test]
+    fn pawns_capture_backwards_in_some_variants() {
+        let (board, american) = moves("W:W14:B18", Variant::American);
+        assert!(american.iter().all(|mv| !mv.is_capture()));
+        for variant in [Variant::Russian, Variant::Brazilian, Variant::Pool] {
+            let (_, moves) = moves("W:W14:B18", variant);
+            assert_eq!(moves.len(), 1, "{variant}");
+            assert_eq!(squares(&board, &moves[0]), vec![14, 23], "{variant}");
+        }
+    }
+
+    #[test]
+    fn flying_kings_move_and_capture_from_afar() {
+        // a king in the corner of the long diagonal
+        let (_, american) = moves("W:WK29:B32", Variant::American);
+        assert_eq!(american.len(), 1);
+        let (_, pool) = moves("W:WK29:B32", Variant::Pool);
+        assert_eq!(pool.len(), 7);
+
+        // 11 is four cells away, and the king can land on either cell after it
+        let (_, american) = moves("W:WK29:B11", Variant::American);
+        assert!(american.iter().all(|mv| !mv.is_capture()));
+        let (board, mut pool) = moves("W:WK29:B11", Variant::Pool);
+        pool.sort_by_key(|mv| squares(&board, mv));
+        let landings = pool
+            .iter()
+            .map(|mv| squares(&board, mv))
+            .collect::<Vec<_>>();
+        assert_eq!(landings, vec![vec![29, 4], vec![29, 8]]);
+    }
+
+    #[test]
+    fn promotion_during_a_capture_depends_on_the_variant() {
+        // the pawn on 11 captures 7 and reaches the back row on 2, from where 6 could be taken
+        let fen = "W:W11:B6,7";
+        // american: crowned, and the move stops
+        let (board, american) = moves(fen, Variant::American);
+        assert_eq!(squares(&board, &american[0]), vec![11, 2]);
+        assert!(promotes(&board, &american[0]));
+        // russian: crowned, and it goes on capturing as a flying king, landing on 9 or 13
+        let (board, russian) = moves(fen, Variant::Russian);
+        assert_eq!(russian.len(), 2);
+        assert!(
+            russian
+                .iter()
+                .all(|mv| mv.eaten().len() == 2 && promotes(&board, mv))
+        );
+        // brazilian: it goes on capturing as a pawn, and isn't crowned as it ends elsewhere
+        let (board, brazilian) = moves(fen, Variant::Brazilian);
+        assert_eq!(squares(&board, &brazilian[0]), vec![11, 2, 9]);
+        assert!(!promotes(&board, &brazilian[0]));
+    }
+
+    #[test]
+    fn italian_pawns_cant_capture_kings() {
+        // on the mirrored italian board, 10 is next to 14 and 5 behind it
+        let (_, italian) = moves("W:W14:BK10", Variant::Italian);
+        assert!(italian.iter().all(|mv| !mv.is_capture()));
+        let (_, italian) = moves("W:W14:B10", Variant::Italian);
+        assert!(italian.iter().all(|mv| mv.is_capture()));
+    }
+
+    #[test]
+    fn italian_captures_with_a_king_first() {
+        // the pawn on 22 can capture 18, and the king on 32 can capture 28
+        let (board, italian) = moves("W:W22,K32:B18,28", Variant::Italian);
+        assert_eq!(italian.len(), 1);
+        assert_eq!(squares(&board, &italian[0]), vec![32, 23]);
+    }
+}
@@ -0,0 +1,586 @@
+//! Endgame tablebases for American checkers: the outcome of every position with a few pieces
+//! left under perfect play, and how many plies it takes, computed by retrograde analysis.
+//!
+//! Positions are grouped in tables by material, the pawns and kings of each side, and a table
+//! covers both players to move. Moves leaving a table capture a piece or crown a pawn, so a table
+//! only depends on tables with fewer pieces or fewer pawns, which are generated first. Tables
+//! with a stronger top player are not stored: their positions are looked up rotated.
+//!
+//! The tables ignore the move limit and repetitions: a won position may take longer to win
+//! than the draw rules allow.
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::{
+    bitboard::{BOTTOM_ROW, BitBoard, Direction, TOP_ROW, bits},
+    board::{Board, Position},
+    player::PlayerId,
+};
+
+/// the directory the tables are usually saved to
+pub const TABLEBASES_DIR: &str = "tablebases";
+/// the longest a game can go on in a table, in plies, for it to fit in a byte
+pub const MAX_PLIES: usize = 254;
+
+const MAGIC: &[u8; 4] = b"CKTB";
+const FORMAT_VERSION: u8 = 1;
+// the players of the positions in the tables, the bottom one first
+const PLAYERS: [PlayerId; 2] = [1, 2];
+// pawns can't stand on the row they are crowned on
+const BOTTOM_PAWN_SQUARES: u32 = !TOP_ROW;
+const TOP_PAWN_SQUARES: u32 = !BOTTOM_ROW;
+
+/// what the player to move gets with perfect play, and in how many plies the game ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win(usize),
+    Loss(usize),
+    Draw,
+}
+impl Outcome {
+    /// the outcome for the player who just moved, one ply earlier
+    pub fn for_previous_mover(self) -> Outcome {
+        match self {
+            Outcome::Win(plies) => Outcome::Loss(plies + 1),
+            Outcome::Loss(plies) => Outcome::Win(plies + 1),
+            Outcome::Draw => Outcome::Draw,
+        }
+    }
+
+    // stored as one byte: 0 for a draw, the plies to the end plus one otherwise. The player who
+    // moves last wins, so odd distances are wins and even ones losses
+    fn decode(byte: u8) -> Outcome {
+        match byte {
+            0 => Outcome::Draw,
+            byte if byte % 2 == 0 => Outcome::Win(byte as usize - 1),
+            byte => Outcome::Loss(byte as usize - 1),
+        }
+    }
+    fn encode(plies: usize) -> u8 {
+        plies as u8 + 1
+    }
+}
+
+/// the pawns and kings of each player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Material {
+    pub bottom_pawns: u32,
+    pub bottom_kings: u32,
+    pub top_pawns: u32,
+    pub top_kings: u32,
+}
+impl Material {
+    pub fn of(board: &BitBoard) -> Material {
+        Material {
+            bottom_pawns: (board.bottom & !board.kings).count_ones(),
+            bottom_kings: (board.bottom & board.kings).count_ones(),
+            top_pawns: (board.top & !board.kings).count_ones(),
+            top_kings: (board.top & board.kings).count_ones(),
+        }
+    }
+    pub fn pieces(&self) -> usize {
+        (self.bottom_pawns + self.bottom_kings + self.top_pawns + self.top_kings) as usize
+    }
+    fn pawns(&self) -> u32 {
+        self.bottom_pawns + self.top_pawns
+    }
+
+    /// whether this material has a table of its own, rather than being looked up rotated
+    fn is_stored(&self) -> bool {
+        (self.bottom_pawns, self.bottom_kings) >= (self.top_pawns, self.top_kings)
+    }
+
+    /// the stored materials with up to `max_pieces` pieces and at least one for each player, in
+    /// the order they can be generated
+    pub fn all(max_pieces: usize) -> Vec<Material> {
+        let mut materials = vec![];
+        for pieces in 2..=max_pieces as u32 {
+            for bottom in 1..pieces {
+                let top = pieces - bottom;
+                for bottom_pawns in 0..=bottom {
+                    for top_pawns in 0..=top {
+                        let material = Material {
+                            bottom_pawns,
+                            bottom_kings: bottom - bottom_pawns,
+                            top_pawns,
+                            top_kings: top - top_pawns,
+                        };
+                        if material.is_stored() {
+                            materials.push(material);
+                        }
+                    }
+                }
+            }
+        }
+        materials.sort_by_key(|material| (material.pieces(), material.pawns()));
+        materials
+    }
+
+    /// the placements of the pieces, some of them impossible because two pawns overlap
+    fn placements(&self) -> usize {
+        let pawns = self.bottom_pawns + self.top_pawns;
+        binomial(28, self.bottom_pawns)
+            * binomial(28, self.top_pawns)
+            * binomial(32 - pawns, self.bottom_kings)
+            * binomial(32 - pawns - self.bottom_kings, self.top_kings)
+    }
+
+    pub fn file_name(&self) -> String {
+        format!(
+            "{}p{}k-{}p{}k.tb",
+            self.bottom_pawns, self.bottom_kings, self.top_pawns, self.top_kings
+        )
+    }
+
+    // pawns are placed first, each side on the squares they can stand on, then the kings on
+    // the squares left
+    fn placement(&self, board: &BitBoard) -> usize {
+        let bottom_pawns = board.bottom & !board.kings;
+        let top_pawns = board.top & !board.kings;
+        let bottom_kings = board.bottom & board.kings;
+        let free = !(bottom_pawns | top_pawns);
+        let pawns = self.bottom_pawns + self.top_pawns;
+
+        let mut index = rank(compress(bottom_pawns, BOTTOM_PAWN_SQUARES));
+        index = index * binomial(28, self.top_pawns) + rank(compress(top_pawns, TOP_PAWN_SQUARES));
+        index =
+            index * binomial(32 - pawns, self.bottom_kings) + rank(compress(bottom_kings, free));
+        index * binomial(32 - pawns - self.bottom_kings, self.top_kings)
+            + rank(compress(board.top & board.kings, free & !bottom_kings))
+    }
+    fn board(&self, mut placement: usize) -> Option<BitBoard> {
+        let pawns = self.bottom_pawns + self.top_pawns;
+        let top_kings_count = binomial(32 - pawns - self.bottom_kings, self.top_kings);
+        let top_kings = placement % top_kings_count;
+        placement /= top_kings_count;
+        let bottom_kings_count = binomial(32 - pawns, self.bottom_kings);
+        let bottom_kings = placement % bottom_kings_count;
+        placement /= bottom_kings_count;
+        let top_pawns_count = binomial(28, self.top_pawns);
+        let top_pawns = expand(
+            unrank(placement % top_pawns_count, self.top_pawns),
+            TOP_PAWN_SQUARES,
+        );
+        let bottom_pawns = expand(
+            unrank(placement / top_pawns_count, self.bottom_pawns),
+            BOTTOM_PAWN_SQUARES,
+        );
+        if bottom_pawns & top_pawns != 0 {
+            return None;
+        }
+
+        let free = !(bottom_pawns | top_pawns);
+        let bottom_kings = expand(unrank(bottom_kings, self.bottom_kings), free);
+        let top_kings = expand(unrank(top_kings, self.top_kings), free & !bottom_kings);
+        Some(BitBoard::new(
+            bottom_pawns | bottom_kings,
+            top_pawns | top_kings,
+            bottom_kings | top_kings,
+            PLAYERS,
+        ))
+    }
+}
+
+/// the tables of every material up to a number of pieces
+#[derive(Debug, Default)]
+pub struct Tablebases {
+    max_pieces: usize,
+    /// for each material, the outcomes of the positions with the bottom player to move followed
+    /// by the ones with the top player to move
+    tables: HashMap<Material, Vec<u8>>,
+}
+
+impl Tablebases {
+    pub fn generate(max_pieces: usize) -> Result<Tablebases, String> {
+        Self::generate_with(max_pieces, |_| {})
+    }
+    /// generates the tables, calling `progress` after each one
+    pub fn generate_with(
+        max_pieces: usize,
+        mut progress: impl FnMut(Material),
+    ) -> Result<Tablebases, String> {
+        let mut tablebases = Tablebases {
+            max_pieces,
+            tables: HashMap::new(),
+        };
+        for material in Material::all(max_pieces) {
+            let table = tablebases.solve(material)?;
+            tablebases.tables.insert(material, table);
+            progress(material);
+        }
+        Ok(tablebases)
+    }
+
+    /// the tables of kings alone, far quicker to generate than all of them: positions with
+    /// pawns are not found in them
+    pub fn generate_kings(max_pieces: usize) -> Result<Tablebases, String> {
+        let mut tablebases = Tablebases {
+            max_pieces,
+            tables: HashMap::new(),
+        };
+        // tables of kings only depend on each other, no move crowning a pawn
+        for material in Material::all(max_pieces) {
+            if material.pawns() == 0 {
+                let table = tablebases.solve(material)?;
+                tablebases.tables.insert(material, table);
+            }
+        }
+        Ok(tablebases)
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// the outcome of the position with `player_id` to move, if it has few enough pieces
+    pub fn probe(&self, board: &BitBoard, player_id: PlayerId) -> Option<Outcome> {
+        let [bottom, _] = board.players();
+        let own = if player_id == bottom {
+            board.bottom
+        } else {
+            board.top
+        };
+        if own == 0 {
+            return Some(Outcome::Loss(0));
+        }
+        let material = Material::of(board);
+        if material.pieces() > self.max_pieces {
+            return None;
+        }
+        let (board, material) = if material.is_stored() {
+            (*board, material)
+        } else {
+            let rotated = board.rotated();
+            (rotated, Material::of(&rotated))
+        };
+        let table = self.tables.get(&material)?;
+        let side = usize::from(player_id != board.players()[0]);
+        let index = side * material.placements() + material.placement(&board);
+        Some(Outcome::decode(table[index]))
+    }
+    /// like `probe`, for boards of American checkers only
+    pub fn probe_board(&self, board: &Board, player_id: PlayerId) -> Option<Outcome> {
+        self.probe(&BitBoard::try_from(board).ok()?, player_id)
+    }
+
+    /// writes each table to its own file in `dir`
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|err| format!("Cannot create {}: {err}", dir.display()))?;
+        for (material, table) in self.tables.iter() {
+            let path = dir.join(material.file_name());
+            let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + table.len());
+            bytes.extend_from_slice(MAGIC);
+            bytes.push(FORMAT_VERSION);
+            bytes.extend_from_slice(table);
+            fs::write(&path, bytes)
+                .map_err(|err| format!("Cannot write {}: {err}", path.display()))?;
+        }
+        Ok(())
+    }
+    /// reads the tables saved in `dir`, up to the largest number of pieces all tables are there for
+    pub fn load(dir: &Path) -> Result<Tablebases, String> {
+        let mut tablebases = Tablebases::default();
+        let mut pieces = 2;
+        loop {
+            let materials = Material::all(pieces)
+                .into_iter()
+                .filter(|material| material.pieces() == pieces)
+                .collect::<Vec<_>>();
+            if !materials
+                .iter()
+                .all(|material| dir.join(material.file_name()).exists())
+            {
+                break;
+            }
+            for material in materials {
+                let table = Self::read_table(&dir.join(material.file_name()), material)?;
+                tablebases.tables.insert(material, table);
+            }
+            tablebases.max_pieces = pieces;
+            pieces += 1;
+        }
+        if tablebases.tables.is_empty() {
+            return Err(format!("No tablebases in {}", dir.display()));
+        }
+        Ok(tablebases)
+    }
+    fn read_table(path: &Path, material: Material) -> Result<Vec<u8>, String> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+        let Some(table) = bytes.strip_prefix(MAGIC) else {
+            return Err(format!("{} is not a tablebase", path.display()));
+        };
+        match table.split_first() {
+            Some((&FORMAT_VERSION, table)) if table.len() == 2 * material.placements() => {
+                Ok(table.to_vec())
+            }
+            Some((&FORMAT_VERSION, _)) => Err(format!("{} is truncated", path.display())),
+            _ => Err(format!(
+                "Unsupported tablebase version in {}",
+                path.display()
+            )),
+        }
+    }
+
+    /// solves a table by retrograde analysis: the positions ending the game in n plies are found
+    /// from the ones ending it in n - 1, going back from the positions without moves and the
+    /// moves to tables solved already
+    fn solve(&self, material: Material) -> Result<Vec<u8>, String> {
+        let placements = material.placements();
+        let positions = 2 * placements;
+        let position = |index: usize| {
+            let board = material.board(index % placements)?;
+            Some((board, PLAYERS[index / placements]))
+        };
+        let index = |board: &BitBoard, player_id: PlayerId| {
+            usize::from(player_id == PLAYERS[1]) * placements + material.placement(board)
+        };
+
+        let mut table = vec![0u8; positions];
+        // moves staying in the table, until they turn out to be won by the opponent
+        let mut pending = vec![0u8; positions];
+        // a move leaving the table wins or draws, so the position can't be lost
+        let mut safe = vec![false; positions];
+        // the longest loss through the moves leaving the table
+        let mut longest = vec![0usize; positions];
+        // positions that end the game in as many plies as the index, as far as known yet
+        let mut candidates = vec![Vec::new(); MAX_PLIES + 1];
+        let push = |candidates: &mut Vec<Vec<usize>>, index: usize, plies: usize| {
+            if plies > MAX_PLIES {
+                return Err(format!(
+                    "{} takes more than {MAX_PLIES} plies to end",
+                    material.file_name()
+                ));
+            }
+            candidates[plies].push(index);
+            Ok(())
+        };
+
+        for i in 0..positions {
+            let Some((board, player_id)) = position(i) else {
+                continue;
+            };
+            let opponent = opponent(player_id);
+            let mut win = None;
+            for mv in board.legal_moves(player_id) {
+                let mut child = board;
+                child.apply(&mv)?;
+                if Material::of(&child) == material {
+                    pending[i] += 1;
+                    continue;
+                }
+                match self.probe(&child, opponent) {
+                    Some(Outcome::Loss(plies)) => {
+                        win = Some(win.unwrap_or(usize::MAX).min(plies + 1))
+                    }
+                    Some(Outcome::Win(plies)) => longest[i] = longest[i].max(plies + 1),
+                    Some(Outcome::Draw) => safe[i] = true,
+                    None => return Err(format!("Missing the table of {:?}", Material::of(&child))),
+                }
+            }
+            if let Some(plies) = win {
+                safe[i] = true;
+                push(&mut candidates, i, plies)?;
+            } else if pending[i] == 0 && !safe[i] {
+                push(&mut candidates, i, longest[i])?;
+            }
+        }
+
+        for plies in 0..=MAX_PLIES {
+            for i in std::mem::take(&mut candidates[plies]) {
+                if table[i] != 0 {
+                    continue;
+                }
+                table[i] = Outcome::encode(plies);
+                let (board, player_id) = position(i).unwrap();
+                let opponent = opponent(player_id);
+                for parent in predecessors(&board, opponent) {
+                    let parent = index(&parent, opponent);
+                    if table[parent] != 0 {
+                        continue;
+                    }
+                    if plies % 2 == 0 {
+                        push(&mut candidates, parent, plies + 1)?;
+                    } else {
+                        pending[parent] -= 1;
+                        if pending[parent] == 0 && !safe[parent] {
+                            push(&mut candidates, parent, longest[parent].max(plies + 1))?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(table)
+    }
+}
+
+fn opponent(player_id: PlayerId) -> PlayerId {
+    if player_id == PLAYERS[0] {
+        PLAYERS[1]
+    } else {
+        PLAYERS[0]
+    }
+}
+
+/// the positions `player_id` could have reached `board` from with a move staying in the same
+/// table: a step that neither captures nor crowns, while no capture was available
+fn predecessors(board: &BitBoard, player_id: PlayerId) -> Vec<BitBoard> {
+    let moves_up = player_id == board.players()[0];
+    let own = if moves_up { board.bottom } else { board.top };
+    let empty = board.empty();
+    let mut parents = vec![];
+    for piece in bits(own) {
+        let is_king = board.kings & piece != 0;
+        for dir in Direction::ALL {
+            if !is_king && dir.is_up() != moves_up {
+                continue;
+            }
+            let from = dir.opposite().shift(piece) & empty;
+            if from == 0 {
+                continue;
+            }
+            let step = |side: u32| side ^ piece ^ from;
+            let parent = BitBoard::new(
+                if moves_up {
+                    step(board.bottom)
+                } else {
+                    board.bottom
+                },
+                if moves_up { board.top } else { step(board.top) },
+                if is_king {
+                    step(board.kings)
+                } else {
+                    board.kings
+                },
+                board.players(),
+            );
+            if parent.jumpers(player_id) == 0 {
+                parents.push(parent);
+            }
+        }
+    }
+    parents
+}
+
+const BINOMIALS: [[usize; 33]; 33] = {
+    let mut table = [[0; 33]; 33];
+    let mut n = 0;
+    while n < 33 {
+        table[n][0] = 1;
+        let mut k = 1;
+        while k <= n {
+            table[n][k] = table[n - 1][k - 1] + table[n - 1][k];
+            k += 1;
+        }
+        n += 1;
+    }
+    table
+};
+fn binomial(n: u32, k: u32) -> usize {
+    if k > n {
+        0
+    } else {
+        BINOMIALS[n as usize][k as usize]
+    }
+}
+
+/// the bits of `set` that are in `among`, packed together
+fn compress(set: u32, among: u32) -> u32 {
+    bits(among)
+        .enumerate()
+        .filter(|(_, bit)| set & bit != 0)
+        .fold(0, |acc, (i, _)| acc | 1 << i)
+}
+/// the reverse of `compress`: spreads the low bits of `packed` over the bits of `among`
+fn expand(packed: u32, among: u32) -> u32 {
+    bits(among)
+        .enumerate()
+        .filter(|(i, _)| packed & 1 << i != 0)
+        .fold(0, |acc, (_, bit)| acc | bit)
+}
+/// the index of a set among the sets of the same size, in colexicographic order
+fn rank(set: u32) -> usize {
+    bits(set)
+        .enumerate()
+        .map(|(i, bit)| binomial(bit.trailing_zeros(), i as u32 + 1))
+        .sum()
+}
+fn unrank(mut rank: usize, size: u32) -> u32 {
+    let mut set = 0;
+    for k in (1..=size).rev() {
+        let mut n = k - 1;
+        while binomial(n + 1, k) <= rank {
+            n += 1;
+        }
+        rank -= binomial(n, k);
+        set |= 1 << n;
+    }
+    set
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Variant;
+
+    fn probe(tablebases: &Tablebases, fen: &str) -> Outcome {
+        let (board, turn) = Board::from_fen(fen, Variant::American, PLAYERS).unwrap();
+        tablebases.probe_board(&board, turn).unwrap()
+    }
+    #[test]
+    fn sets_rank_and_unrank_back() {
+        for size in 0..=4 {
+            for rank in (0..binomial(32, size)).step_by(7) {
+                let set = unrank(rank, size);
+                assert_eq!(set.count_ones(), size);
+                assert_eq!(super::rank(set), rank);
+            }
+        }
+        let among = BOTTOM_PAWN_SQUARES;
+        for set in [0, 1 << 4, 1 << 4 | 1 << 17 | 1 << 31] {
+            assert_eq!(expand(compress(set, among), among), set);
+        }
+    }
+
+    #[test]
+    fn placements_index_their_boards_back() {
+        for material in Material::all(3) {
+            let mut boards = 0;
+            for placement in 0..material.placements() {
+                let Some(board) = material.board(placement) else {
+                    continue;
+                };
+                assert_eq!(Material::of(&board), material);
+                assert_eq!(material.placement(&board), placement, "{material:?}");
+                boards += 1;
+            }
+            assert!(boards > 0, "{material:?}");
+        }
+    }
+
+    #[test]
+    fn a_king_each_is_a_draw() {
+        let tablebases = Tablebases::generate(2).unwrap();
+        assert_eq!(probe(&tablebases, "W:WK14:BK28"), Outcome::Draw);
+        assert_eq!(probe(&tablebases, "B:WK14:BK28"), Outcome::Draw);
+        // unless a king is left to be captured
+        assert_eq!(probe(&tablebases, "W:WK14:BK10"), Outcome::Win(1));
+        assert_eq!(probe(&tablebases, "B:WK14:BK10"), Outcome::Win(1));
+    }
+
+    #[test]
+    fn two_kings_beat_one() {
+        let tablebases = Tablebases::generate_kings(3).unwrap();
+        for fen in ["W:WK14,K18:BK1", "W:WK29,K30:BK4", "B:WK29,K30:BK4"] {
+            match probe(&tablebases, fen) {
+                Outcome::Win(plies) if fen.starts_with('W') => assert!(plies % 2 == 1, "{fen}"),
+                Outcome::Loss(plies) if fen.starts_with('B') => assert!(plies % 2 == 0, "{fen}"),
+                outcome => panic!("{fen}: {outcome:?}"),
+            }
+        }
+        // the same, seen from the other side of the board
+        assert!(matches!(
+            probe(&tablebases, "B:WK1:BK14,K18"),
+            Outcome::Win(_)
+        ));
+    }
+}
@@ -0,0 +1,61 @@
+//! A fixed-size hash table of search results, keyed by `zobrist::position_hash`. Each key maps
+//! to one slot, and a new entry replaces the old one unless the old one comes from a deeper
+//! search of another position.
+
+/// what a search can keep about a position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry<T> {
+    pub hash: u64,
+    /// how deep the position was searched
+    pub depth: usize,
+    pub value: T,
+}
+
+#[derive(Debug, Clone)]
+pub struct TranspositionTable<T> {
+    slots: Vec<Option<Entry<T>>>,
+    // slots.len() - 1, as the number of slots is a power of two
+    mask: usize,
+}
+impl<T> TranspositionTable<T> {
+    /// a table of at least `entries` slots, rounded up to a power of two
+    pub fn new(entries: usize) -> Self {
+        let size = entries.max(1).next_power_of_two();
+        Self {
+            slots: (0..size).map(|_| None).collect(),
+            mask: size - 1,
+        }
+    }
+    /// a table using about `megabytes` of memory
+    pub fn with_megabytes(megabytes: usize) -> Self {
+        let entry = size_of::<Option<Entry<T>>>().max(1);
+        // rounding up could double the size, so aim at half of it
+        Self::new((megabytes << 20) / entry / 2)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+    fn slot(&self, hash: u64) -> usize {
+        hash as usize & self.mask
+    }
+
+    /// the entry of the position, if it is still in the table
+    pub fn get(&self, hash: u64) -> Option<&Entry<T>> {
+        self.slots[self.slot(hash)]
+            .as_ref()
+            .filter(|entry| entry.hash == hash)
+    }
+    pub fn insert(&mut self, hash: u64, depth: usize, value: T) {
+        let slot = self.slot(hash);
+        let keep = self.slots[slot]
+            .as_ref()
+            .is_some_and(|entry| entry.hash != hash && entry.depth > depth);
+        if !keep {
+            self.slots[slot] = Some(Entry { hash, depth, value });
+        }
+    }
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+    }
+}
@@ -0,0 +1,146 @@
+//! Zobrist hashing: every (cell, piece) pair gets a random key, and a position hashes to the
+//! xor of the keys of its pieces, so a move only has to xor in and out the cells it changes.
+//! Keys are fixed, so hashes are the same across runs and machines.
+use crate::{board::Board, piece::Piece, player::PlayerId};
+
+/// cells on the biggest board supported, 12x12
+pub const MAX_CELLS: usize = 144;
+
+// bottom pawn, bottom king, top pawn, top king
+const KINDS: usize = 4;
+
+/// splitmix64, good enough to spread the keys
+const fn next_key(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn generate_keys() -> ([[u64; KINDS]; MAX_CELLS], u64) {
+    let mut keys = [[0; KINDS]; MAX_CELLS];
+    let mut state = 0x636b_6572_5321;
+    let mut cell = 0;
+    while cell < MAX_CELLS {
+        let mut kind = 0;
+        while kind < KINDS {
+            let (next, key) = next_key(state);
+            keys[cell][kind] = key;
+            state = next;
+            kind += 1;
+        }
+        cell += 1;
+    }
+    let (_, side) = next_key(state);
+    (keys, side)
+}
+
+static KEYS: ([[u64; KINDS]; MAX_CELLS], u64) = generate_keys();
+
+/// the key of `piece` standing on the cell with index `cell`, see `game_utils::coords_to_index`
+pub fn piece_key(cell: usize, piece: Piece, is_bottom_player: bool) -> u64 {
+    let kind = match (is_bottom_player, piece.is_king()) {
+        (true, false) => 0,
+        (true, true) => 1,
+        (false, false) => 2,
+        (false, true) => 3,
+    };
+    KEYS.0[cell][kind]
+}
+
+/// xored in when the top player is the one to move
+pub fn side_key() -> u64 {
+    KEYS.1
+}
+
+/// the hash of the board with `player_id` to move: the identity of a position
+pub fn position_hash(board: &Board, player_id: PlayerId) -> u64 {
+    if player_id == board.players()[0] {
+        board.hash()
+    } else {
+        board.hash() ^ side_key()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::SmallRng, seq::SliceRandom};
+
+    use super::*;
+    use crate::{
+        game_utils::{coords_to_index, legal_moves},
+        rules::Variant,
+    };
+
+    const PLAYERS: [PlayerId; 2] = [1, 2];
+
+    /// the hash computed from scratch, from every piece on the board
+    fn full_hash(board: &Board, player_id: PlayerId) -> u64 {
+        let pieces = board.coords().filter_map(|coords| {
+            let piece = board[coords]?;
+            let cell = coords_to_index(coords, board.width);
+            Some(piece_key(cell, piece, piece.player_id == PLAYERS[0]))
+        });
+        let side = if player_id == PLAYERS[0] {
+            0
+        } else {
+            side_key()
+        };
+        pieces.fold(side, |hash, key| hash ^ key)
+    }
+
+    #[test]
+    fn hashes_follow_random_moves_and_undos() {
+        let mut rng = SmallRng::seed_from_u64(0x5eed);
+        let (mut crownings, mut multi_captures) = (0, 0);
+        for variant in Variant::ALL {
+            for _ in 0..20 {
+                let mut board = Board::starting_position(variant, PLAYERS);
+                let mut turn = PLAYERS[0];
+                let mut played = vec![];
+                for _ in 0..300 {
+                    let moves = legal_moves(&board, turn);
+                    // mostly forward, so that games get to crowning, with undos on the way
+                    if !played.is_empty() && (moves.is_empty() || rng.gen_bool(0.3)) {
+                        let (record, hash) = played.pop().unwrap();
+                        board.undo(&record).unwrap();
+                        turn = if turn == PLAYERS[0] {
+                            PLAYERS[1]
+                        } else {
+                            PLAYERS[0]
+                        };
+                        assert_eq!(position_hash(&board, turn), hash, "{variant}");
+                    } else if let Some(mv) = moves.choose(&mut rng) {
+                        let hash = position_hash(&board, turn);
+                        let record = board.play(mv).unwrap();
+                        crownings += record.crowned as usize;
+                        multi_captures += (record.captured.len() > 1) as usize;
+                        played.push((record, hash));
+                        turn = if turn == PLAYERS[0] {
+                            PLAYERS[1]
+                        } else {
+                            PLAYERS[0]
+                        };
+                    } else {
+                        break;
+                    }
+                    assert_eq!(
+                        position_hash(&board, turn),
+                        full_hash(&board, turn),
+                        "{variant}"
+                    );
+                }
+            }
+        }
+        assert!(crownings > 0 && multi_captures > 0);
+    }
+
+    #[test]
+    fn the_side_to_move_changes_the_hash() {
+        let board = Board::starting_position(Variant::American, PLAYERS);
+        assert_eq!(position_hash(&board, PLAYERS[0]), board.hash());
+        assert_eq!(position_hash(&board, PLAYERS[1]), board.hash() ^ side_key());
+        assert_eq!(Board::empty(Variant::American, PLAYERS).hash(), 0);
+    }
+}
@@ -0,0 +1,77 @@
+//! move generation checked against published perft numbers, from the starting position
+use store::{
+    bitboard::BitBoard,
+    board::Board,
+    perft::{divide, perft},
+    rules::Variant,
+};
+
+const PLAYERS: [u64; 2] = [1, 2];
+
+// American checkers
+const AMERICAN: [u64; 9] = [7, 49, 302, 1469, 7361, 36768, 179740, 845931, 3963680];
+// International draughts
+const INTERNATIONAL: [u64; 6] = [9, 81, 658, 4265, 27117, 167140];
+
+#[test]
+fn american_board() {
+    let board = Board::starting_position(Variant::American, PLAYERS);
+    for (depth, expected) in AMERICAN.iter().enumerate().take(6) {
+        assert_eq!(
+            perft(&board, PLAYERS[0], depth + 1),
+            *expected,
+            "depth {}",
+            depth + 1
+        );
+    }
+}
+
+#[test]
+fn american_bitboard() {
+    let board = Board::starting_position(Variant::American, PLAYERS);
+    let bitboard = BitBoard::try_from(&board).unwrap();
+    for (depth, expected) in AMERICAN.iter().enumerate().take(8) {
+        assert_eq!(
+            perft(&bitboard, PLAYERS[0], depth + 1),
+            *expected,
+            "depth {}",
+            depth + 1
+        );
+    }
+}
+
+#[test]
+fn international_board() {
+    let board = Board::starting_position(Variant::International, PLAYERS);
+    for (depth, expected) in INTERNATIONAL.iter().enumerate().take(5) {
+        assert_eq!(
+            perft(&board, PLAYERS[0], depth + 1),
+            *expected,
+            "depth {}",
+            depth + 1
+        );
+    }
+}
+
+#[test]
+fn divide_adds_up() {
+    let board = Board::starting_position(Variant::American, PLAYERS);
+    let bitboard = BitBoard::try_from(&board).unwrap();
+    let moves = divide(&board, PLAYERS[0], 5);
+    assert_eq!(moves.len(), 7);
+    assert_eq!(
+        moves.iter().map(|(_, nodes)| nodes).sum::<u64>(),
+        AMERICAN[4]
+    );
+    // both representations agree on every first move, maybe in a different order
+    let bitboard_moves = divide(&bitboard, PLAYERS[0], 5);
+    assert_eq!(bitboard_moves.len(), moves.len());
+    assert!(bitboard_moves.iter().all(|split| moves.contains(split)));
+}
+
+#[test]
+fn depth_zero() {
+    let board = Board::starting_position(Variant::American, PLAYERS);
+    assert_eq!(perft(&board, PLAYERS[0], 0), 1);
+    assert!(divide(&board, PLAYERS[0], 0).is_empty());
+}